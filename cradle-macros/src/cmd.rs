@@ -0,0 +1,390 @@
+//! Parsing and expansion of command lines for the `cmd!` macro.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::iter::Peekable;
+use syn::Expr;
+
+pub(crate) enum Part {
+    Literal(String),
+    Expr(Box<Expr>),
+}
+
+pub(crate) type Word = Vec<Part>;
+
+pub(crate) fn parse_command_line(command_line: &str) -> Result<Vec<Word>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<Word> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = command_line.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                push_char(&mut word, '{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                push_char(&mut word, '}');
+            }
+            '{' => {
+                let expression = parse_interpolation(&mut chars)?;
+                word.get_or_insert_with(Vec::new)
+                    .push(Part::Expr(Box::new(expression)));
+            }
+            '}' => return Err("unmatched '}' in command line, use '}}' for a literal '}'".into()),
+            '\'' | '"' if quote == Some(char) => quote = None,
+            '\'' | '"' if quote.is_none() => {
+                quote = Some(char);
+                word.get_or_insert_with(Vec::new);
+            }
+            char if char.is_whitespace() && quote.is_none() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            char => push_char(&mut word, char),
+        }
+    }
+    if let Some(quote) = quote {
+        return Err(format!("unterminated quote ({}) in command line", quote));
+    }
+    if let Some(word) = word {
+        words.push(word);
+    }
+    if words.is_empty() {
+        return Err("command line must not be empty".into());
+    }
+    Ok(words)
+}
+
+fn push_char(word: &mut Option<Word>, char: char) {
+    let word = word.get_or_insert_with(Vec::new);
+    match word.last_mut() {
+        Some(Part::Literal(literal)) => literal.push(char),
+        _ => word.push(Part::Literal(char.to_string())),
+    }
+}
+
+/// Parses the expression of an interpolation, up to its closing `}`.
+/// Braces in string and character literals don't count.
+fn parse_interpolation<I>(chars: &mut Peekable<I>) -> Result<Expr, String>
+where
+    I: Iterator<Item = char>,
+{
+    let mut source = String::new();
+    let mut depth = 0;
+    loop {
+        let char = chars
+            .next()
+            .ok_or("unmatched '{' in command line, use '{{' for a literal '{'")?;
+        match char {
+            '}' if depth == 0 => break,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' => {
+                let raw_string_hashes = raw_string_hashes(&source);
+                source.push(char);
+                read_string_literal(chars, &mut source, raw_string_hashes)?;
+                continue;
+            }
+            '\'' => {
+                source.push(char);
+                read_char_literal(chars, &mut source);
+                continue;
+            }
+            _ => {}
+        }
+        source.push(char);
+    }
+    if source.trim().is_empty() {
+        return Err("empty interpolation '{}' in command line".into());
+    }
+    syn::parse_str(&source)
+        .map_err(|error| format!("invalid expression '{}' in command line: {}", source, error))
+}
+
+/// If `source` ends with the prefix of a raw string literal
+/// (e.g. `r` or `br##`), returns the number of `#`s.
+fn raw_string_hashes(source: &str) -> Option<usize> {
+    let hashes = source.chars().rev().take_while(|char| *char == '#').count();
+    let mut prefix = source.chars().rev().skip(hashes);
+    if prefix.next() != Some('r') {
+        return None;
+    }
+    let mut before = prefix.next();
+    if before == Some('b') {
+        before = prefix.next();
+    }
+    match before {
+        Some(char) if char.is_alphanumeric() || char == '_' => None,
+        _ => Some(hashes),
+    }
+}
+
+/// Reads a string literal after its opening `"` into `source`.
+fn read_string_literal<I>(
+    chars: &mut Peekable<I>,
+    source: &mut String,
+    raw_string_hashes: Option<usize>,
+) -> Result<(), String>
+where
+    I: Iterator<Item = char>,
+{
+    loop {
+        let char = chars
+            .next()
+            .ok_or("unterminated string literal in command line")?;
+        source.push(char);
+        match (char, raw_string_hashes) {
+            ('\\', None) => source.extend(chars.next()),
+            ('"', None) => return Ok(()),
+            ('"', Some(hashes)) => {
+                let mut closing_hashes = 0;
+                while closing_hashes < hashes && chars.peek() == Some(&'#') {
+                    source.extend(chars.next());
+                    closing_hashes += 1;
+                }
+                if closing_hashes == hashes {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads a character literal after its opening `'` into `source`.
+/// Lifetimes and labels (e.g. `'a`) are read up to the `'` only.
+fn read_char_literal<I>(chars: &mut Peekable<I>, source: &mut String)
+where
+    I: Iterator<Item = char>,
+{
+    match chars.next() {
+        Some('\\') => {
+            source.push('\\');
+            source.extend(chars.next());
+            for char in chars.by_ref() {
+                source.push(char);
+                if char == '\'' {
+                    break;
+                }
+            }
+        }
+        Some(char) => {
+            source.push(char);
+            if chars.peek() == Some(&'\'') {
+                source.extend(chars.next());
+            }
+        }
+        None => {}
+    }
+}
+
+pub(crate) fn expand(words: Vec<Word>) -> TokenStream {
+    let mut words = words.into_iter().rev().map(expand_word);
+    let last = words.next().expect("command line is not empty");
+    words.fold(last, |tail, head| quote!((#head, #tail)))
+}
+
+fn expand_word(word: Word) -> TokenStream {
+    match word.as_slice() {
+        [] => quote!(""),
+        [Part::Literal(literal)] => quote!(#literal),
+        [Part::Expr(expression)] => quote!(&(#expression)),
+        parts => {
+            let pushes = parts.iter().map(|part| match part {
+                Part::Literal(literal) => quote!(__cradle_argument.push(#literal);),
+                Part::Expr(expression) => quote! {
+                    __cradle_argument.push(::std::convert::AsRef::<::std::ffi::OsStr>::as_ref(&(#expression)));
+                },
+            });
+            // The interpolated expressions are evaluated in the scope of the
+            // variable, so it has a name that user code is unlikely to shadow.
+            // (`Span::mixed_site` would require rust 1.45.)
+            quote! {{
+                let mut __cradle_argument = ::std::ffi::OsString::new();
+                #(#pushes)*
+                __cradle_argument
+            }}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(command_line: &str) -> Result<String, String> {
+        parse_command_line(command_line).map(|words| expand(words).to_string())
+    }
+
+    #[test]
+    fn splits_by_whitespace() {
+        assert_eq!(
+            parse(" echo  foo\tbar ").unwrap(),
+            quote!(("echo", ("foo", "bar"))).to_string()
+        );
+    }
+
+    #[test]
+    fn single_words() {
+        assert_eq!(parse("ls").unwrap(), quote!("ls").to_string());
+    }
+
+    #[test]
+    fn interpolates_whole_arguments() {
+        assert_eq!(
+            parse("echo {message}").unwrap(),
+            quote!(("echo", &(message))).to_string()
+        );
+    }
+
+    #[test]
+    fn interpolates_parts_of_arguments() {
+        let as_ref = quote!(::std::convert::AsRef::<::std::ffi::OsStr>::as_ref);
+        assert_eq!(
+            parse("--author={author}").unwrap(),
+            quote!({
+                let mut __cradle_argument = ::std::ffi::OsString::new();
+                __cradle_argument.push("--author=");
+                __cradle_argument.push(#as_ref(&(author)));
+                __cradle_argument
+            })
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn quotes_group_whitespace() {
+        assert_eq!(
+            parse("echo 'foo bar' \"baz  boo\"").unwrap(),
+            quote!(("echo", ("foo bar", "baz  boo"))).to_string()
+        );
+    }
+
+    #[test]
+    fn empty_quotes_are_empty_arguments() {
+        assert_eq!(parse("echo ''").unwrap(), quote!(("echo", "")).to_string());
+    }
+
+    #[test]
+    fn double_braces_are_literal_braces() {
+        assert_eq!(
+            parse("echo {{foo}}").unwrap(),
+            quote!(("echo", "{foo}")).to_string()
+        );
+    }
+
+    #[test]
+    fn allows_braces_in_expressions() {
+        assert_eq!(
+            parse("echo {if a { b } else { c }}").unwrap(),
+            quote!(("echo", &(if a { b } else { c }))).to_string()
+        );
+    }
+
+    #[test]
+    fn allows_braces_in_string_literals() {
+        assert_eq!(
+            parse(r#"echo {"}"} {"{"}"#).unwrap(),
+            quote!(("echo", (&("}"), &("{")))).to_string()
+        );
+    }
+
+    #[test]
+    fn allows_escaped_quotes_in_string_literals() {
+        assert_eq!(
+            parse(r#"echo {"\"}"}"#).unwrap(),
+            quote!(("echo", &("\"}"))).to_string()
+        );
+    }
+
+    #[test]
+    fn allows_braces_in_raw_string_literals() {
+        assert_eq!(
+            parse(r##"echo {r#""}"#}"##).unwrap(),
+            quote!(("echo", &(r#""}"#))).to_string()
+        );
+    }
+
+    #[test]
+    fn allows_braces_in_char_literals() {
+        assert_eq!(
+            parse(r"echo {'{'} {'}'} {'\''}").unwrap(),
+            quote!(("echo", (&('{'), (&('}'), &('\''))))).to_string()
+        );
+    }
+
+    #[test]
+    fn allows_lifetimes_in_expressions() {
+        assert_eq!(
+            parse("echo {'a: loop { break 'a x; }}").unwrap(),
+            quote!((
+                "echo",
+                &('a: loop {
+                    break 'a x;
+                })
+            ))
+            .to_string()
+        );
+    }
+
+    mod errors {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn empty_command_lines() {
+            assert_eq!(parse(" ").unwrap_err(), "command line must not be empty");
+        }
+
+        #[test]
+        fn unterminated_quotes() {
+            assert_eq!(
+                parse("echo 'foo").unwrap_err(),
+                "unterminated quote (') in command line"
+            );
+        }
+
+        #[test]
+        fn unmatched_opening_braces() {
+            assert_eq!(
+                parse("echo {foo").unwrap_err(),
+                "unmatched '{' in command line, use '{{' for a literal '{'"
+            );
+        }
+
+        #[test]
+        fn unmatched_closing_braces() {
+            assert_eq!(
+                parse("echo foo}").unwrap_err(),
+                "unmatched '}' in command line, use '}}' for a literal '}'"
+            );
+        }
+
+        #[test]
+        fn empty_interpolations() {
+            assert_eq!(
+                parse("echo {}").unwrap_err(),
+                "empty interpolation '{}' in command line"
+            );
+        }
+
+        #[test]
+        fn unterminated_string_literals() {
+            assert_eq!(
+                parse(r#"echo {"foo}"#).unwrap_err(),
+                "unterminated string literal in command line"
+            );
+        }
+
+        #[test]
+        fn invalid_expressions() {
+            assert!(parse("echo {1 +}")
+                .unwrap_err()
+                .starts_with("invalid expression '1 +' in command line"));
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Expansion of `#[derive(Input)]`.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    spanned::Spanned, Data, DeriveInput, Error, Field, Fields, GenericArgument, Ident, Lit, Meta,
+    NestedMeta, PathArguments, Type,
+};
+
+pub(crate) fn expand(input: DeriveInput) -> Result<TokenStream, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span,
+                "Input can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "Input can only be derived for structs",
+            ))
+        }
+    };
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let bindings = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => Ident::new(&format!("field_{}", unraw(ident)), ident.span()),
+            None => Ident::new(&format!("field_{}", index), field.span()),
+        })
+        .collect::<Vec<_>>();
+    let pattern = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!(#name { #(#names: #bindings,)* })
+        }
+        Fields::Unnamed(_) => quote!(#name ( #(#bindings,)* )),
+        Fields::Unit => quote!(#name),
+    };
+    let configure_fields = fields
+        .iter()
+        .zip(&bindings)
+        .map(|(field, binding)| configure_field(field, binding))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(quote! {
+        impl #impl_generics ::cradle::input::Input for #name #type_generics #where_clause {
+            #[doc(hidden)]
+            fn configure(self, config: &mut ::cradle::config::Config) {
+                let #pattern = self;
+                #(#configure_fields)*
+            }
+        }
+    })
+}
+
+#[derive(Debug, Default)]
+struct Attributes {
+    positional: bool,
+    rename: Option<String>,
+}
+
+fn parse_attributes(field: &Field) -> Result<Attributes, Error> {
+    let mut result = Attributes::default();
+    for attribute in &field.attrs {
+        if !attribute.path.is_ident("cradle") {
+            continue;
+        }
+        let list = match attribute.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new(meta.span(), "expected #[cradle(...)]")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("positional") => {
+                    result.positional = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("rename") =>
+                {
+                    match name_value.lit {
+                        Lit::Str(string) => result.rename = Some(string.value()),
+                        lit => return Err(Error::new(lit.span(), "expected a string literal")),
+                    }
+                }
+                nested => {
+                    return Err(Error::new(
+                        nested.span(),
+                        "unknown attribute, expected 'positional' or 'rename = \"...\"'",
+                    ))
+                }
+            }
+        }
+    }
+    if result.positional && result.rename.is_some() {
+        return Err(Error::new(
+            field.span(),
+            "positional arguments can't be renamed",
+        ));
+    }
+    Ok(result)
+}
+
+/// The kinds of field types that are treated specially.
+enum Kind {
+    Bool,
+    Option,
+    Vec,
+    Other,
+}
+
+fn kind(ty: &Type) -> Kind {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    };
+    match segment {
+        Some(segment) if segment.ident == "bool" && segment.arguments.is_empty() => Kind::Bool,
+        Some(segment) if segment.ident == "Option" && has_one_type_argument(&segment.arguments) => {
+            Kind::Option
+        }
+        Some(segment) if segment.ident == "Vec" && has_one_type_argument(&segment.arguments) => {
+            Kind::Vec
+        }
+        _ => Kind::Other,
+    }
+}
+
+fn has_one_type_argument(arguments: &PathArguments) -> bool {
+    match arguments {
+        PathArguments::AngleBracketed(arguments) => match arguments.args.first() {
+            Some(GenericArgument::Type(_)) => arguments.args.len() == 1,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn configure_field(field: &Field, binding: &Ident) -> Result<TokenStream, Error> {
+    let attributes = parse_attributes(field)?;
+    let configure = |value: TokenStream| quote!(::cradle::input::Input::configure(#value, config););
+    let flag = match (&field.ident, attributes.rename) {
+        (Some(_), _) if attributes.positional => None,
+        (None, _) => None,
+        (Some(_), Some(rename)) => Some(rename),
+        (Some(ident), None) => Some(format!("--{}", unraw(ident).replace('_', "-"))),
+    };
+    let configure_flag = flag.as_ref().map(|flag| configure(flag.to_token_stream()));
+    Ok(match kind(&field.ty) {
+        Kind::Bool => match flag {
+            Some(_) => quote! {
+                if #binding {
+                    #configure_flag
+                }
+            },
+            None => {
+                return Err(Error::new(
+                    field.span(),
+                    "bool fields can't be positional arguments",
+                ))
+            }
+        },
+        Kind::Option => {
+            let configure_value = configure(quote!(value));
+            quote! {
+                if let ::std::option::Option::Some(value) = #binding {
+                    #configure_flag
+                    #configure_value
+                }
+            }
+        }
+        Kind::Vec => {
+            let configure_value = configure(quote!(value));
+            quote! {
+                for value in #binding {
+                    #configure_flag
+                    #configure_value
+                }
+            }
+        }
+        Kind::Other => {
+            let configure_value = configure(binding.to_token_stream());
+            quote! {
+                #configure_flag
+                #configure_value
+            }
+        }
+    })
+}
+
+fn unraw(ident: &Ident) -> String {
+    ident.to_string().trim_start_matches("r#").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    fn expand_error(input: DeriveInput) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_enums() {
+        assert_eq!(
+            expand_error(parse_quote!(
+                enum Foo {}
+            )),
+            "Input can only be derived for structs"
+        );
+    }
+
+    #[test]
+    fn rejects_positional_bools() {
+        assert_eq!(
+            expand_error(parse_quote!(
+                struct Foo {
+                    #[cradle(positional)]
+                    foo: bool,
+                }
+            )),
+            "bool fields can't be positional arguments"
+        );
+    }
+
+    #[test]
+    fn rejects_renamed_positional_arguments() {
+        assert_eq!(
+            expand_error(parse_quote!(
+                struct Foo {
+                    #[cradle(positional, rename = "--bar")]
+                    foo: String,
+                }
+            )),
+            "positional arguments can't be renamed"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_attributes() {
+        assert_eq!(
+            expand_error(parse_quote!(
+                struct Foo {
+                    #[cradle(bar)]
+                    foo: String,
+                }
+            )),
+            "unknown attribute, expected 'positional' or 'rename = \"...\"'"
+        );
+    }
+
+    #[test]
+    fn rejects_non_string_renames() {
+        assert_eq!(
+            expand_error(parse_quote!(
+                struct Foo {
+                    #[cradle(rename = 42)]
+                    foo: String,
+                }
+            )),
+            "expected a string literal"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Expansion of `#[derive(Output)]`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields};
+
+pub(crate) fn expand(input: DeriveInput) -> Result<TokenStream, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span,
+                "Output can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "Output can only be derived for structs",
+            ))
+        }
+    };
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let field_types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let from_child_output = |ty| {
+        quote! {
+            <#ty as ::cradle::output::Output>::from_child_output(config, child_output)?
+        }
+    };
+    let construction = match fields {
+        Fields::Named(fields) => {
+            let fields = fields.named.iter().map(|field| {
+                let name = &field.ident;
+                let value = from_child_output(&field.ty);
+                quote!(#name: #value)
+            });
+            quote!(#name { #(#fields,)* })
+        }
+        Fields::Unnamed(fields) => {
+            let fields = fields
+                .unnamed
+                .iter()
+                .map(|field| from_child_output(&field.ty));
+            quote!(#name ( #(#fields,)* ))
+        }
+        Fields::Unit => quote!(#name),
+    };
+    Ok(quote! {
+        impl #impl_generics ::cradle::output::Output for #name #type_generics #where_clause {
+            #[doc(hidden)]
+            fn configure(config: &mut ::cradle::config::Config) {
+                #(<#field_types as ::cradle::output::Output>::configure(config);)*
+            }
+
+            #[doc(hidden)]
+            fn from_child_output(
+                config: &::cradle::config::Config,
+                child_output: &::cradle::child_output::ChildOutput,
+            ) -> ::std::result::Result<Self, ::cradle::error::Error> {
+                ::std::result::Result::Ok(#construction)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    #[test]
+    fn rejects_enums() {
+        let error = expand(parse_quote!(
+            enum Foo {}
+        ))
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Output can only be derived for structs");
+    }
+
+    #[test]
+    fn rejects_unions() {
+        let error = expand(parse_quote!(
+            union Foo {
+                a: u8,
+            }
+        ))
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Output can only be derived for structs");
+    }
+}
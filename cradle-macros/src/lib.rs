@@ -0,0 +1,154 @@
+#![deny(missing_debug_implementations)]
+
+//! Procedural macros for [`cradle`](https://docs.rs/cradle).
+//!
+//! Don't depend on this crate directly.
+//! Instead, enable `cradle`'s `macros` feature,
+//! which re-exports the macros from here.
+
+mod cmd;
+mod derive_input;
+mod derive_output;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, LitStr};
+
+/// Parses a command line at compile time into an
+/// [`Input`](https://docs.rs/cradle/latest/cradle/input/trait.Input.html)
+/// that can be passed to `run!`, `run_output!` and `run_result!`:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let message = "hello world";
+/// let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n {message}"));
+/// assert_eq!(output, "hello world\n");
+/// ```
+///
+/// The command line is split into arguments by whitespace,
+/// like [`Split`](https://docs.rs/cradle/latest/cradle/input/struct.Split.html) does.
+/// Rust expressions in curly braces are interpolated.
+/// They are never split, so they always end up in a single argument,
+/// even if they contain whitespace.
+///
+/// An expression that makes up a whole argument can be any
+/// [`Input`](https://docs.rs/cradle/latest/cradle/input/trait.Input.html) that implements `Clone`.
+/// It is passed in by reference, so it's not moved.
+/// Expressions that are combined with other text into one argument,
+/// like in `--author={author}`, must implement `AsRef<OsStr>`:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let name = "foo bar";
+/// let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n --name={name} {name}"));
+/// assert_eq!(output, "--name=foo bar\nfoo bar\n");
+/// ```
+///
+/// Single and double quotes group text with whitespace into one argument,
+/// and `{{` and `}}` are literal braces:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n 'foo bar' {{baz}}"));
+/// assert_eq!(output, "foo bar\n{baz}\n");
+/// ```
+///
+/// Unterminated quotes, unbalanced braces and invalid expressions
+/// are compile errors.
+#[proc_macro]
+pub fn cmd(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    match cmd::parse_command_line(&literal.value()) {
+        Ok(words) => cmd::expand(words).into(),
+        Err(message) => syn::Error::new(literal.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// Derives [`Output`](https://docs.rs/cradle/latest/cradle/output/trait.Output.html)
+/// for structs whose fields all implement `Output`.
+/// This allows to use named fields instead of tuples
+/// for collecting multiple outputs of a child process:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// #[derive(Output)]
+/// struct Outputs {
+///     stdout: StdoutTrimmed,
+///     stderr: Stderr,
+///     status: Status,
+/// }
+///
+/// let outputs: Outputs = run_output!("sh", "-c", "echo foo; echo bar >&2; exit 42");
+/// assert_eq!(outputs.stdout.0, "foo");
+/// assert_eq!(outputs.stderr.0, "bar\n");
+/// assert_eq!(outputs.status.0.code(), Some(42));
+/// ```
+///
+/// Each field is configured and collected as if it was used on its own,
+/// just like the elements of tuples.
+/// Tuple structs and unit structs are supported as well.
+#[proc_macro_derive(Output)]
+pub fn derive_output(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive_output::expand(input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+/// Derives [`Input`](https://docs.rs/cradle/latest/cradle/input/trait.Input.html)
+/// for structs that describe command line options.
+/// This allows to model the options of a command once, and reuse them:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// #[derive(Input)]
+/// struct Ls {
+///     #[cradle(positional)]
+///     executable: &'static str,
+///     all: bool,
+///     #[cradle(rename = "-I")]
+///     ignore: Vec<&'static str>,
+///     sort: Option<&'static str>,
+///     #[cradle(positional)]
+///     directory: String,
+/// }
+///
+/// let ls = Ls {
+///     executable: "ls",
+///     all: true,
+///     ignore: vec!["*.tmp", "*.bak"],
+///     sort: None,
+///     directory: ".".to_string(),
+/// };
+/// # let ls = ("echo", ls);
+/// let StdoutTrimmed(output) = run_output!(ls);
+/// # assert_eq!(output, "ls --all -I *.tmp -I *.bak .");
+/// // runs 'ls --all -I *.tmp -I *.bak .'
+/// ```
+///
+/// The fields are turned into arguments in the order they are declared:
+///
+/// - By default, fields become options: `--name value`.
+///   The option name is the field name, with underscores replaced by dashes.
+/// - `bool` fields become flags: `--name` if `true`, nothing if `false`.
+/// - `Option` fields are omitted if `None`.
+/// - `Vec` fields repeat the option for every element.
+/// - `#[cradle(rename = "...")]` replaces the option, e.g. with a short option like `-v`.
+/// - `#[cradle(positional)]` passes only the value, without an option in front of it.
+///   Fields of tuple structs are always positional.
+///
+/// Values can be of any type that implements
+/// [`Input`](https://docs.rs/cradle/latest/cradle/input/trait.Input.html).
+#[proc_macro_derive(Input, attributes(cradle))]
+pub fn derive_input(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive_input::expand(input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
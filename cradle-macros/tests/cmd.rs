@@ -0,0 +1,65 @@
+use cradle::prelude::*;
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+
+#[test]
+fn splits_literal_text() {
+    let StdoutTrimmed(output) = run_output!(cmd!("echo foo bar"));
+    assert_eq!(output, "foo bar");
+}
+
+#[test]
+fn does_not_split_interpolated_expressions() {
+    let message = "foo bar";
+    let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n {message}"));
+    assert_eq!(output, "foo bar\n");
+}
+
+#[test]
+fn does_not_move_interpolated_expressions() {
+    let message = String::from("foo");
+    let StdoutTrimmed(output) = run_output!(cmd!("echo {message}"));
+    assert_eq!(output, message);
+}
+
+#[test]
+fn interpolates_parts_of_arguments() {
+    let author = "foo bar";
+    let path = PathBuf::from("baz");
+    let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n --author={author} {path}/boo"));
+    assert_eq!(output, "--author=foo bar\nbaz/boo\n");
+}
+
+#[test]
+fn interpolates_variables_named_like_internal_variables() {
+    let argument = "foo";
+    let StdoutTrimmed(output) = run_output!(cmd!("echo --a={argument}"));
+    assert_eq!(output, "--a=foo");
+}
+
+#[test]
+fn interpolates_arbitrary_expressions() {
+    let words = ["foo", "bar"];
+    let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n {words[1]} {words.join(\"-\")}"));
+    assert_eq!(output, "bar\nfoo-bar\n");
+}
+
+#[test]
+fn interpolates_expressions_with_braces_in_literals() {
+    let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n {\"}\"} {'{'}"));
+    assert_eq!(output, "}\n{\n");
+}
+
+#[test]
+fn interpolates_inputs() {
+    let StdoutTrimmed(output) = run_output!(cmd!("cat"), cmd!("{Stdin(\"foo\")}"));
+    assert_eq!(output, "foo");
+}
+
+#[test]
+fn works_with_all_run_macros() {
+    run!(cmd!("true"));
+    let () = run_result!(cmd!("true")).unwrap();
+    let Status(status) = run_output!(cmd!("false"));
+    assert!(!status.success());
+}
@@ -0,0 +1,181 @@
+use cradle::prelude::*;
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+
+fn arguments(input: impl Input) -> String {
+    let StdoutTrimmed(output) = run_output!("echo", input);
+    output
+}
+
+#[test]
+fn options() {
+    #[derive(Input)]
+    struct Options {
+        foo: &'static str,
+        bar_baz: String,
+    }
+    assert_eq!(
+        arguments(Options {
+            foo: "a",
+            bar_baz: "b".to_string()
+        }),
+        "--foo a --bar-baz b"
+    );
+}
+
+#[test]
+fn bool_flags() {
+    #[derive(Input)]
+    struct Options {
+        foo: bool,
+        bar: bool,
+    }
+    assert_eq!(
+        arguments(Options {
+            foo: true,
+            bar: false
+        }),
+        "--foo"
+    );
+}
+
+#[test]
+fn optional_options() {
+    #[derive(Input)]
+    struct Options {
+        foo: Option<&'static str>,
+        bar: Option<&'static str>,
+    }
+    assert_eq!(
+        arguments(Options {
+            foo: None,
+            bar: Some("b")
+        }),
+        "--bar b"
+    );
+}
+
+#[test]
+fn repeated_options() {
+    #[derive(Input)]
+    struct Options {
+        foo: Vec<&'static str>,
+    }
+    assert_eq!(
+        arguments(Options {
+            foo: vec!["a", "b"]
+        }),
+        "--foo a --foo b"
+    );
+    assert_eq!(arguments(Options { foo: vec![] }), "");
+}
+
+#[test]
+fn renamed_options() {
+    #[derive(Input)]
+    struct Options {
+        #[cradle(rename = "-v")]
+        verbose: bool,
+        #[cradle(rename = "-e")]
+        env: Vec<&'static str>,
+    }
+    assert_eq!(
+        arguments(Options {
+            verbose: true,
+            env: vec!["A=a"]
+        }),
+        "-v -e A=a"
+    );
+}
+
+#[test]
+fn positional_arguments() {
+    #[derive(Input)]
+    struct Options {
+        #[cradle(positional)]
+        command: &'static str,
+        foo: bool,
+        #[cradle(positional)]
+        files: Vec<PathBuf>,
+        #[cradle(positional)]
+        last: Option<&'static str>,
+    }
+    assert_eq!(
+        arguments(Options {
+            command: "run",
+            foo: true,
+            files: vec![PathBuf::from("a"), PathBuf::from("b")],
+            last: Some("c"),
+        }),
+        "run --foo a b c"
+    );
+}
+
+#[test]
+fn tuple_structs() {
+    #[derive(Input)]
+    struct Options(&'static str, Option<&'static str>);
+    assert_eq!(arguments(Options("foo", Some("bar"))), "foo bar");
+}
+
+#[test]
+fn unit_structs() {
+    #[derive(Input)]
+    struct Options;
+    assert_eq!(arguments(Options), "");
+}
+
+#[test]
+fn raw_identifiers() {
+    #[derive(Input)]
+    struct Options {
+        r#type: &'static str,
+    }
+    assert_eq!(arguments(Options { r#type: "foo" }), "--type foo");
+}
+
+#[test]
+fn nested_inputs() {
+    #[derive(Input)]
+    struct Inner {
+        foo: bool,
+    }
+    #[derive(Input)]
+    struct Outer {
+        #[cradle(positional)]
+        inner: Inner,
+        bar: bool,
+    }
+    assert_eq!(
+        arguments(Outer {
+            inner: Inner { foo: true },
+            bar: true
+        }),
+        "--foo --bar"
+    );
+}
+
+#[test]
+fn other_inputs_as_values() {
+    #[derive(Input)]
+    struct Options {
+        #[cradle(positional)]
+        stdin: Stdin<&'static str>,
+    }
+    let StdoutTrimmed(output) = run_output!(
+        "cat",
+        Options {
+            stdin: Stdin("foo")
+        }
+    );
+    assert_eq!(output, "foo");
+}
+
+#[test]
+fn generic_structs() {
+    #[derive(Input)]
+    struct Options<T: Input> {
+        foo: T,
+    }
+    assert_eq!(arguments(Options { foo: "a" }), "--foo a");
+}
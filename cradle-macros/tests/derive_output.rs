@@ -0,0 +1,84 @@
+use cradle::prelude::*;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn named_fields() {
+    #[derive(Output)]
+    struct Outputs {
+        stdout: StdoutUntrimmed,
+        stderr: Stderr,
+        exit: Exit,
+    }
+    let outputs: Outputs = run_output!("sh", "-c", "echo foo; echo bar >&2; exit 42");
+    assert_eq!(outputs.stdout.0, "foo\n");
+    assert_eq!(outputs.stderr.0, "bar\n");
+    assert_eq!(outputs.exit, Exit::Code(42));
+}
+
+#[test]
+fn tuple_structs() {
+    #[derive(Output)]
+    struct Outputs(StdoutTrimmed, Status);
+    let Outputs(StdoutTrimmed(stdout), Status(status)) = run_output!(%"echo foo");
+    assert_eq!(stdout, "foo");
+    assert!(status.success());
+}
+
+#[test]
+fn unit_structs() {
+    #[derive(Output)]
+    struct Nothing;
+    let Nothing = run_output!(%"echo foo");
+}
+
+#[test]
+fn nested_structs() {
+    #[derive(Output)]
+    struct Inner {
+        stdout: StdoutTrimmed,
+    }
+    #[derive(Output)]
+    struct Outer {
+        inner: Inner,
+        status: Status,
+    }
+    let outer: Outer = run_output!(%"echo foo");
+    assert_eq!(outer.inner.stdout.0, "foo");
+    assert!(outer.status.0.success());
+}
+
+#[test]
+fn generic_structs() {
+    #[derive(Output)]
+    struct WithStatus<T: Output> {
+        output: T,
+        status: Status,
+    }
+    let result: WithStatus<StdoutTrimmed> = run_output!(%"echo foo");
+    assert_eq!(result.output.0, "foo");
+    assert!(result.status.0.success());
+}
+
+#[test]
+fn errors_are_passed_through() {
+    #[derive(Output, Debug)]
+    #[allow(dead_code)]
+    struct Outputs {
+        stdout: StdoutUntrimmed,
+    }
+    let result: Result<Outputs, Error> = run_result!("false");
+    match result {
+        Err(Error::NonZeroExitCode { .. }) => {}
+        _ => panic!("should match Error::NonZeroExitCode"),
+    }
+}
+
+#[test]
+fn works_with_handles() {
+    #[derive(Output)]
+    struct Outputs {
+        stdout: StdoutTrimmed,
+    }
+    let handle: Handle<Outputs> = spawn!(%"echo foo").unwrap();
+    assert_eq!(handle.wait().unwrap().stdout.0, "foo");
+}
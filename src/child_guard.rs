@@ -0,0 +1,138 @@
+//! An internal module that makes sure child processes don't outlive
+//! the code that spawned them.
+
+use crate::config::Config;
+#[cfg(unix)]
+use crate::{config::ProcessGroup, input::GracefulStop, pty::WindowSizeForwarder};
+#[cfg(unix)]
+use nix::{
+    sys::signal::{kill, killpg, Signal},
+    unistd::Pid,
+};
+#[cfg(unix)]
+use std::{
+    fs::File,
+    thread,
+    time::{Duration, Instant},
+};
+use std::{
+    io,
+    process::{Child, ExitStatus},
+};
+
+/// Wraps a running [`Child`]. When dropped before the child has been
+/// waited for (e.g. while unwinding from a panic), the child is killed,
+/// unless that was disabled with [`KillOnDrop`](crate::input::KillOnDrop).
+///
+/// If the child was started in its own process group or session,
+/// the whole group is signalled.
+#[derive(Debug)]
+pub(crate) struct ChildGuard {
+    child: Child,
+    kill_on_drop: bool,
+    #[cfg(unix)]
+    signal_process_group: bool,
+    #[cfg(unix)]
+    graceful_stop: GracefulStop,
+    /// The master side of the child's pseudo-terminal, if it has one.
+    /// Closing the master side sends `SIGHUP` to the child.
+    /// Children may close their standard streams before exiting,
+    /// so this is only closed after the child has been waited for.
+    #[cfg(unix)]
+    pty_master: Option<File>,
+    #[cfg(unix)]
+    window_size_forwarder: Option<WindowSizeForwarder>,
+}
+
+impl ChildGuard {
+    pub(crate) fn new(child: Child, config: &Config) -> Self {
+        ChildGuard {
+            child,
+            kill_on_drop: config.kill_on_drop,
+            #[cfg(unix)]
+            signal_process_group: config.process_group != ProcessGroup::Inherited || config.pty,
+            #[cfg(unix)]
+            graceful_stop: config.graceful_stop,
+            #[cfg(unix)]
+            pty_master: None,
+            #[cfg(unix)]
+            window_size_forwarder: None,
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn keep_pty_master_open(&mut self, master: File) {
+        self.pty_master = Some(master);
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn forward_window_size(&mut self, forwarder: WindowSizeForwarder) {
+        self.window_size_forwarder = Some(forwarder);
+    }
+
+    pub(crate) fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    pub(crate) fn child_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    pub(crate) fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait()
+    }
+
+    /// Asks the child to terminate with the configured [`GracefulStop`] signal,
+    /// and kills it with `SIGKILL` if it's still running after the grace period.
+    /// On non-unix platforms the child is killed right away.
+    #[cfg(unix)]
+    pub(crate) fn terminate(&mut self) -> io::Result<()> {
+        if self.child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        self.signal(self.graceful_stop.signal)?;
+        let deadline = Instant::now() + self.graceful_stop.grace;
+        while Instant::now() < deadline {
+            if self.child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.signal(Signal::SIGKILL)?;
+        self.child.wait()?;
+        Ok(())
+    }
+
+    /// Sends `signal` to the child, or to its whole process group,
+    /// if it has its own.
+    #[cfg(unix)]
+    pub(crate) fn signal(&self, signal: Signal) -> io::Result<()> {
+        let pid = Pid::from_raw(self.child.id() as i32);
+        if self.signal_process_group {
+            // The child is the leader of its process group,
+            // so the group id is the child's pid.
+            killpg(pid, signal)?;
+        } else {
+            kill(pid, signal)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn terminate(&mut self) -> io::Result<()> {
+        if self.child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        self.child.kill()?;
+        self.child.wait()?;
+        Ok(())
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if self.kill_on_drop {
+            let _ = self.terminate();
+        }
+    }
+}
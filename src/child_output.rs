@@ -1,7 +1,18 @@
 //! An internal module used for the outputs of child processes.
 
 use crate::{
-    collected_output::Waiter, config::Config, context::Context, error::Error, output::Output,
+    child_guard::ChildGuard,
+    collected_output::Waiter,
+    config::Config,
+    context::Context,
+    error::Error,
+    output::{Exit, Output},
+    which,
+};
+#[cfg(unix)]
+use crate::{
+    config::ProcessGroup,
+    pty::{self, MasterReader, MasterWriter, Pty, WindowSizeForwarder},
 };
 use std::{
     ffi::OsString,
@@ -32,61 +43,171 @@ impl ChildOutput {
         T: Output,
     {
         <T as Output>::configure(&mut config);
-        let child_output = ChildOutput::run_child_process(context, &config)?;
+        let (child, waiter) = ChildOutput::spawn_child_process(context, &config)?;
+        let child_output = ChildOutput::wait_for_child_process(&config, child, waiter)?;
         T::from_child_output(&config, &child_output)
     }
 
-    fn run_child_process<Stdout, Stderr>(
+    pub(crate) fn spawn_child_process<Stdout, Stderr>(
         mut context: Context<Stdout, Stderr>,
         config: &Config,
-    ) -> Result<Self, Error>
+    ) -> Result<(ChildGuard, Waiter), Error>
     where
         Stdout: Write + Clone + Send + 'static,
         Stderr: Write + Clone + Send + 'static,
     {
         let (executable, arguments) = Self::parse_input(config.arguments.clone())?;
+        if let Some(error) = &config.configuration_error {
+            return Err(Error::from_configuration_error(config, error));
+        }
         if config.log_command {
             writeln!(context.stderr, "+ {}", config.full_command())
                 .map_err(|error| Error::command_io_error(config, error))?;
         }
-        let mut command = Command::new(&executable);
+        let resolved = which::resolve_executable(&executable, config)?;
+        let mut command = Command::new(&resolved);
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.arg0(&executable);
+        }
         command.args(arguments);
-        for (key, value) in &config.added_environment_variables {
-            command.env(key, value);
+        config.configure_environment(&mut command);
+        #[cfg(unix)]
+        let pty = if config.pty {
+            Some(
+                Pty::open(config.pty_size)
+                    .map_err(|error| Error::command_io_error(config, error))?,
+            )
+        } else {
+            None
+        };
+        #[cfg(unix)]
+        match &pty {
+            Some(pty) => {
+                let slave_stdio = || {
+                    pty.slave_stdio()
+                        .map_err(|error| Error::command_io_error(config, error))
+                };
+                command
+                    .stdin(slave_stdio()?)
+                    .stdout(slave_stdio()?)
+                    .stderr(slave_stdio()?);
+            }
+            None => Self::configure_standard_streams(&mut command, config),
         }
-        command
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        #[cfg(not(unix))]
+        Self::configure_standard_streams(&mut command, config);
         if let Some(working_directory) = &config.working_directory {
             command.current_dir(working_directory);
         }
-        let mut child = command.spawn().map_err(|source| {
+        #[cfg(unix)]
+        Self::configure_process_group(&mut command, config);
+        let child = command.spawn().map_err(|source| {
             if source.kind() == std::io::ErrorKind::NotFound {
-                Error::FileNotFound { executable, source }
+                Error::file_not_found(
+                    config,
+                    &executable,
+                    source,
+                    which::diagnose_not_found(&resolved),
+                )
             } else {
                 Error::command_io_error(config, source)
             }
         })?;
-        let waiter = Waiter::spawn_standard_stream_relaying(
-            &context,
+        // Dropping `command` closes the parent's copies of the pseudo-terminal's
+        // slave side. Otherwise reading from the master would never finish.
+        drop(command);
+        let mut child = ChildGuard::new(child, config);
+        #[cfg(unix)]
+        {
+            if let Some(pty) = pty {
+                let waiter = Self::relay_pty(&context, config, &mut child, pty)?;
+                return Ok((child, waiter));
+            }
+        }
+        let waiter = Self::relay_pipes(&context, config, &mut child);
+        Ok((child, waiter))
+    }
+
+    fn configure_standard_streams(command: &mut Command, config: &Config) {
+        let stdio = |inherit: bool| {
+            if inherit {
+                Stdio::inherit()
+            } else {
+                Stdio::piped()
+            }
+        };
+        command
+            .stdin(stdio(config.inherit_stdin && config.stdin.is_none()))
+            .stdout(stdio(config.inherit_stdout && !config.capture_stdout))
+            .stderr(stdio(config.inherit_stderr && !config.capture_stderr));
+    }
+
+    #[cfg(unix)]
+    fn relay_pty<Stdout, Stderr>(
+        context: &Context<Stdout, Stderr>,
+        config: &Config,
+        child: &mut ChildGuard,
+        pty: Pty,
+    ) -> Result<Waiter, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        let master = pty.into_master();
+        let try_clone = || {
+            master
+                .try_clone()
+                .map_err(|error| Error::command_io_error(config, error))
+        };
+        child.keep_pty_master_open(try_clone()?);
+        if config.pty_size.is_none() {
+            child.forward_window_size(WindowSizeForwarder::spawn(
+                try_clone()?,
+                pty::parent_window_size,
+            ));
+        }
+        let master_writer = try_clone()?;
+        Ok(Waiter::spawn_standard_stream_relaying(
+            context,
             config,
-            child.stdin.take().expect("child process should have stdin"),
-            child
-                .stdout
-                .take()
-                .expect("child process should have stdout"),
-            child
-                .stderr
-                .take()
-                .expect("child process should have stderr"),
-        );
-        let exit_status = child
-            .wait()
-            .map_err(|error| Error::command_io_error(config, error))?;
+            Some(MasterWriter::new(master_writer)),
+            Some(MasterReader(master)),
+            Some(std::io::empty()),
+        ))
+    }
+
+    fn relay_pipes<Stdout, Stderr>(
+        context: &Context<Stdout, Stderr>,
+        config: &Config,
+        child: &mut ChildGuard,
+    ) -> Waiter
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        let child = child.child_mut();
+        Waiter::spawn_standard_stream_relaying(
+            context,
+            config,
+            child.stdin.take(),
+            child.stdout.take(),
+            child.stderr.take(),
+        )
+    }
+
+    pub(crate) fn wait_for_child_process(
+        config: &Config,
+        mut child: ChildGuard,
+        waiter: Waiter,
+    ) -> Result<Self, Error> {
         let collected_output = waiter
             .join()
             .map_err(|error| Error::command_io_error(config, error))?;
+        let exit_status = child
+            .wait()
+            .map_err(|error| Error::command_io_error(config, error))?;
         Self::check_exit_status(config, exit_status)?;
         Ok(Self {
             stdout: collected_output.stdout,
@@ -95,6 +216,41 @@ impl ChildOutput {
         })
     }
 
+    #[cfg(unix)]
+    fn configure_process_group(command: &mut Command, config: &Config) {
+        use nix::unistd::{setpgid, setsid, Pid};
+        use std::os::unix::process::CommandExt;
+
+        // `setpgid`, `setsid` and `ioctl` are async-signal-safe,
+        // so they are allowed to be called between `fork` and `exec`.
+        if config.pty {
+            // A process can only acquire a controlling terminal
+            // if it's a session leader.
+            unsafe {
+                command.pre_exec(|| {
+                    setsid()?;
+                    pty::set_controlling_terminal()
+                });
+            }
+            return;
+        }
+        match config.process_group {
+            ProcessGroup::Inherited => {}
+            ProcessGroup::New => unsafe {
+                command.pre_exec(|| {
+                    setpgid(Pid::from_raw(0), Pid::from_raw(0))?;
+                    Ok(())
+                });
+            },
+            ProcessGroup::NewSession => unsafe {
+                command.pre_exec(|| {
+                    setsid()?;
+                    Ok(())
+                });
+            },
+        }
+    }
+
     fn parse_input(
         input: Vec<OsString>,
     ) -> Result<(OsString, impl Iterator<Item = OsString>), Error> {
@@ -112,6 +268,7 @@ impl ChildOutput {
             Err(Error::NonZeroExitCode {
                 full_command: config.full_command(),
                 exit_status,
+                exit: Exit::from(exit_status),
             })
         } else {
             Ok(())
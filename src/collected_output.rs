@@ -1,24 +1,97 @@
 use crate::{config::Config, context::Context};
 use std::{
+    fmt,
     io::{self, Read, Write},
-    process::{ChildStderr, ChildStdin, ChildStdout},
+    panic,
+    sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender},
     thread::{self, JoinHandle},
 };
 
 #[derive(Debug)]
 pub(crate) struct Waiter {
     stdin: Option<JoinHandle<io::Result<()>>>,
-    stdout: JoinHandle<io::Result<Option<Vec<u8>>>>,
-    stderr: JoinHandle<io::Result<Option<Vec<u8>>>>,
+    stdout: Option<JoinHandle<io::Result<Option<Vec<u8>>>>>,
+    stderr: Option<JoinHandle<io::Result<Option<Vec<u8>>>>>,
+    finished: Receiver<Finished>,
+    // Only sessions keep `stdin` open.
+    #[cfg_attr(not(feature = "session"), allow(dead_code))]
+    open_stdin: Option<OpenStdin>,
+    stdout_chunks: Option<Receiver<Vec<u8>>>,
+}
+
+/// How many chunks of `stdout` are buffered for [`Config::stream_stdout`]
+/// before the relaying thread blocks, waiting for them to be received.
+const STDOUT_CHUNKS_CAPACITY: usize = 16;
+
+/// The `stdin` of a child process that was spawned with
+/// [`Config::keep_stdin_open`] set.
+#[cfg_attr(not(feature = "session"), allow(dead_code))]
+pub(crate) struct OpenStdin(pub(crate) Box<dyn Write + Send>);
+
+impl fmt::Debug for OpenStdin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OpenStdin")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stream {
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug)]
+struct Finished {
+    stream: Stream,
+    panicked: bool,
+}
+
+/// Notifies the [`Waiter`] when a relaying thread finishes,
+/// including when it finishes by panicking.
+struct FinishedNotifier {
+    stream: Stream,
+    sender: Sender<Finished>,
+}
+
+impl Drop for FinishedNotifier {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Finished {
+            stream: self.stream,
+            panicked: thread::panicking(),
+        });
+    }
 }
 
 impl Waiter {
+    fn spawn_relaying_thread<T, F>(
+        finished: &Sender<Finished>,
+        stream: Stream,
+        f: F,
+    ) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let notifier = FinishedNotifier {
+            stream,
+            sender: finished.clone(),
+        };
+        thread::spawn(move || {
+            let _notifier = notifier;
+            f()
+        })
+    }
+
     fn spawn_standard_stream_handler(
+        finished: &Sender<Finished>,
+        stream: Stream,
         capture_stream: bool,
         mut source: impl Read + Send + 'static,
         mut relay_sink: impl Write + Send + 'static,
+        chunks: Option<SyncSender<Vec<u8>>>,
     ) -> JoinHandle<io::Result<Option<Vec<u8>>>> {
-        thread::spawn(move || -> io::Result<Option<Vec<u8>>> {
+        Self::spawn_relaying_thread(finished, stream, move || -> io::Result<Option<Vec<u8>>> {
             let mut collected = if capture_stream {
                 Some(Vec::new())
             } else {
@@ -33,63 +106,169 @@ impl Waiter {
                 if let Some(collected) = &mut collected {
                     collected.extend(&buffer[..length]);
                 }
-                if !capture_stream {
-                    relay_sink.write_all(&buffer[..length])?;
+                match &chunks {
+                    // The receiver may have been dropped already,
+                    // in which case the chunk is discarded,
+                    // but the stream should still be drained.
+                    Some(chunks) => {
+                        let _ = chunks.send(buffer[..length].to_vec());
+                    }
+                    None if !capture_stream => relay_sink.write_all(&buffer[..length])?,
+                    None => {}
                 }
             }
             Ok(collected)
         })
     }
 
+    /// Spawns relaying threads for the given standard streams of the child.
+    /// Streams that are `None` -- e.g. because they are inherited from
+    /// the parent -- don't get a thread.
+    ///
+    /// If [`Config::keep_stdin_open`] is set, `stdin` is not written to,
+    /// but stored in the [`Waiter`] as an [`OpenStdin`].
+    /// If [`Config::stream_stdout`] is set, `stdout` is sent in chunks over
+    /// a bounded channel instead of being relayed.
+    /// When nobody receives the chunks, the child blocks on writing to `stdout`,
+    /// until the receiver is dropped.
     pub(crate) fn spawn_standard_stream_relaying<Stdout, Stderr>(
         context: &Context<Stdout, Stderr>,
         config: &Config,
-        mut child_stdin: ChildStdin,
-        child_stdout: ChildStdout,
-        child_stderr: ChildStderr,
+        child_stdin: Option<impl Write + Send + 'static>,
+        child_stdout: Option<impl Read + Send + 'static>,
+        child_stderr: Option<impl Read + Send + 'static>,
     ) -> Self
     where
         Stdout: Write + Send + Clone + 'static,
         Stderr: Write + Send + Clone + 'static,
     {
-        let stdin_join_handle = config.stdin.clone().map(|config_stdin| {
-            thread::spawn(move || -> io::Result<()> {
-                child_stdin.write_all(&config_stdin)?;
-                Ok(())
-            })
+        let (sender, finished) = channel();
+        let (child_stdin, open_stdin) = if config.keep_stdin_open {
+            (None, child_stdin)
+        } else {
+            (child_stdin, None)
+        };
+        let (chunks_sender, chunks_receiver) = if config.stream_stdout {
+            let (sender, receiver) = sync_channel(STDOUT_CHUNKS_CAPACITY);
+            (Some(sender), Some(receiver))
+        } else {
+            (None, None)
+        };
+        let stdin_join_handle = match (config.stdin.clone(), child_stdin) {
+            (Some(config_stdin), Some(mut child_stdin)) => Some(Self::spawn_relaying_thread(
+                &sender,
+                Stream::Stdin,
+                move || -> io::Result<()> {
+                    child_stdin.write_all(&config_stdin)?;
+                    Ok(())
+                },
+            )),
+            _ => None,
+        };
+        let stdout_join_handle = child_stdout.map(|child_stdout| {
+            Self::spawn_standard_stream_handler(
+                &sender,
+                Stream::Stdout,
+                config.capture_stdout,
+                child_stdout,
+                context.stdout.clone(),
+                chunks_sender,
+            )
+        });
+        let stderr_join_handle = child_stderr.map(|child_stderr| {
+            Self::spawn_standard_stream_handler(
+                &sender,
+                Stream::Stderr,
+                config.capture_stderr,
+                child_stderr,
+                context.stderr.clone(),
+                None,
+            )
         });
-        let stdout_join_handle = Self::spawn_standard_stream_handler(
-            config.capture_stdout,
-            child_stdout,
-            context.stdout.clone(),
-        );
-        let stderr_join_handle = Self::spawn_standard_stream_handler(
-            config.capture_stderr,
-            child_stderr,
-            context.stderr.clone(),
-        );
         Waiter {
             stdin: stdin_join_handle,
             stdout: stdout_join_handle,
             stderr: stderr_join_handle,
+            finished,
+            open_stdin: open_stdin.map(|stdin| OpenStdin(Box::new(stdin))),
+            stdout_chunks: chunks_receiver,
         }
     }
 
-    pub(crate) fn join(self) -> io::Result<CollectedOutput> {
+    #[cfg(feature = "session")]
+    pub(crate) fn take_open_stdin(&mut self) -> Option<OpenStdin> {
+        self.open_stdin.take()
+    }
+
+    pub(crate) fn take_stdout_chunks(&mut self) -> Option<Receiver<Vec<u8>>> {
+        self.stdout_chunks.take()
+    }
+
+    /// Waits for all relaying threads to finish.
+    ///
+    /// If one of the threads panics, this doesn't wait for the others,
+    /// but resumes the panic in the calling thread right away.
+    /// That way the child process can be cleaned up while unwinding,
+    /// instead of blocking forever on a child that no one relays for anymore.
+    ///
+    /// Stdout chunks that haven't been taken are discarded from here on.
+    pub(crate) fn join(mut self) -> io::Result<CollectedOutput> {
+        self.stdout_chunks = None;
+        let thread_count = [
+            self.stdin.is_some(),
+            self.stdout.is_some(),
+            self.stderr.is_some(),
+        ]
+        .iter()
+        .filter(|running| **running)
+        .count();
+        for _ in 0..thread_count {
+            match self.finished.recv() {
+                Ok(Finished {
+                    stream,
+                    panicked: true,
+                }) => self.resume_panic(stream),
+                Ok(Finished {
+                    panicked: false, ..
+                }) => {}
+                Err(_) => break,
+            }
+        }
         if let Some(stdin) = self.stdin {
-            stdin.join().expect("stdout relaying thread panicked")?;
+            join_thread(stdin)?;
         }
         Ok(CollectedOutput {
-            stdout: self
-                .stdout
-                .join()
-                .expect("stdout relaying thread panicked")?,
-            stderr: self
-                .stderr
-                .join()
-                .expect("stderr relaying thread panicked")?,
+            stdout: join_output_thread(self.stdout)?,
+            stderr: join_output_thread(self.stderr)?,
         })
     }
+
+    fn resume_panic(self, stream: Stream) -> ! {
+        let payload = match stream {
+            Stream::Stdin => self.stdin.and_then(|stdin| stdin.join().err()),
+            Stream::Stdout => self.stdout.and_then(|stdout| stdout.join().err()),
+            Stream::Stderr => self.stderr.and_then(|stderr| stderr.join().err()),
+        };
+        match payload {
+            Some(payload) => panic::resume_unwind(payload),
+            None => panic!("{:?} relaying thread panicked", stream),
+        }
+    }
+}
+
+fn join_thread<T>(join_handle: JoinHandle<T>) -> T {
+    join_handle
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+fn join_output_thread(
+    join_handle: Option<JoinHandle<io::Result<Option<Vec<u8>>>>>,
+) -> io::Result<Option<Vec<u8>>> {
+    match join_handle {
+        Some(join_handle) => join_thread(join_handle),
+        None => Ok(None),
+    }
 }
 
 #[derive(Debug)]
@@ -1,6 +1,17 @@
 //! An internal module used for configuring child processes.
 
-use std::{ffi::OsString, path::PathBuf, sync::Arc};
+use crate::error::Error;
+#[cfg(unix)]
+use crate::input::{GracefulStop, PtySize};
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    ffi::{OsStr, OsString},
+    fmt, io,
+    path::PathBuf,
+    process::Command,
+    sync::Arc,
+};
 
 /// Used by `Input` implementations to configure how child processes are run.
 /// Usually you don't have to use this type directly.
@@ -8,23 +19,83 @@ use std::{ffi::OsString, path::PathBuf, sync::Arc};
 /// See also the documentation for
 /// [Custom `Input` impls](crate::Input#custom-input-impls) and
 /// [Custom `Output` impls](crate::Output#custom-output-impls).
+///
+/// The `Debug` output redacts [`Secret`](crate::input::Secret)s.
 #[rustversion::attr(since(1.48), allow(clippy::rc_buffer))]
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Config {
     pub(crate) arguments: Vec<OsString>,
+    /// Indices into [`Config::arguments`] that are [`Secret`](crate::input::Secret)s.
+    pub(crate) secret_arguments: BTreeSet<usize>,
     pub(crate) log_command: bool,
     pub(crate) working_directory: Option<PathBuf>,
     pub(crate) added_environment_variables: Vec<(OsString, OsString)>,
+    /// Indices into [`Config::added_environment_variables`] whose values are
+    /// [`Secret`](crate::input::Secret)s.
+    pub(crate) secret_environment_variables: BTreeSet<usize>,
+    /// Environment variables that are removed from the inherited environment.
+    /// They are removed before [`Config::added_environment_variables`] are added.
+    pub(crate) removed_environment_variables: BTreeSet<OsString>,
+    /// Whether the child starts out with an empty environment,
+    /// instead of inheriting the parent's environment.
+    pub(crate) clear_environment: bool,
+    /// Errors that occur in [`Input::configure`](crate::input::Input::configure),
+    /// which can't return errors.
+    /// They are reported when the child process is spawned.
+    pub(crate) configuration_error: Option<ConfigurationError>,
     pub(crate) stdin: Option<Arc<Vec<u8>>>,
+    /// Whether [`Config::stdin`] contains a [`Secret`](crate::input::Secret).
+    pub(crate) secret_stdin: bool,
+    pub(crate) inherit_stdin: bool,
+    pub(crate) inherit_stdout: bool,
+    pub(crate) inherit_stderr: bool,
     pub(crate) capture_stdout: bool,
     pub(crate) capture_stderr: bool,
     pub(crate) error_on_non_zero_exit_code: bool,
+    pub(crate) kill_on_drop: bool,
+    /// Set for [`Session`](crate::session::Session)s.
+    /// Keeps the child's `stdin` open, instead of writing [`Config::stdin`] to it.
+    pub(crate) keep_stdin_open: bool,
+    /// Hands out the child's `stdout` in chunks, instead of relaying it.
+    pub(crate) stream_stdout: bool,
+    #[cfg(unix)]
+    pub(crate) process_group: ProcessGroup,
+    #[cfg(unix)]
+    pub(crate) graceful_stop: GracefulStop,
+    #[cfg(unix)]
+    pub(crate) pty: bool,
+    #[cfg(unix)]
+    pub(crate) pty_size: Option<PtySize>,
+}
+
+/// See [`Config::configuration_error`].
+#[derive(Debug, Clone)]
+pub(crate) enum ConfigurationError {
+    InvalidPathDirectory(PathBuf),
+    ReadEnvFile {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    ParseEnvFile {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+/// Whether a child process is put into its own process group or session.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ProcessGroup {
+    Inherited,
+    New,
+    NewSession,
 }
 
 impl Config {
     pub(crate) fn full_command(&self) -> String {
         let mut result = String::new();
-        for argument in self.arguments.iter() {
+        for argument in self.redacted_arguments() {
             let argument = argument.to_string_lossy();
             if !result.is_empty() {
                 result.push(' ');
@@ -40,19 +111,262 @@ impl Config {
         }
         result
     }
+
+    /// Returns the arguments, with [`Secret`](crate::input::Secret)s replaced by `***`.
+    fn redacted_arguments(&self) -> Vec<&OsStr> {
+        self.arguments
+            .iter()
+            .enumerate()
+            .map(|(index, argument)| {
+                if self.secret_arguments.contains(&index) {
+                    OsStr::new(REDACTED)
+                } else {
+                    argument.as_os_str()
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the added environment variables, with the values of
+    /// [`Secret`](crate::input::Secret)s replaced by `***`.
+    fn redacted_environment_variables(&self) -> Vec<(&OsStr, &OsStr)> {
+        self.added_environment_variables
+            .iter()
+            .enumerate()
+            .map(|(index, (key, value))| {
+                if self.secret_environment_variables.contains(&index) {
+                    (key.as_os_str(), OsStr::new(REDACTED))
+                } else {
+                    (key.as_os_str(), value.as_os_str())
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the `PATH` that the child process will see was set with a
+    /// [`Secret`](crate::input::Secret).
+    pub(crate) fn path_variable_is_secret(&self) -> bool {
+        let index = self
+            .added_environment_variables
+            .iter()
+            .rposition(|(key, _)| is_path_variable(key));
+        match index {
+            Some(index) => self.secret_environment_variables.contains(&index),
+            None => false,
+        }
+    }
+
+    /// Records `error`, unless an earlier error was recorded already.
+    pub(crate) fn add_configuration_error(&mut self, error: ConfigurationError) {
+        self.configuration_error.get_or_insert(error);
+    }
+
+    /// Returns the `PATH` that the child process will see:
+    /// the last `PATH` set with [`Env`](crate::input::Env),
+    /// or the `PATH` of the parent process, unless it was removed
+    /// or the environment was cleared.
+    pub(crate) fn path_variable(&self) -> Option<OsString> {
+        let added = self
+            .added_environment_variables
+            .iter()
+            .rev()
+            .find(|(key, _)| is_path_variable(key))
+            .map(|(_, value)| value.clone());
+        let removed = self
+            .removed_environment_variables
+            .iter()
+            .any(|key| is_path_variable(key));
+        match added {
+            Some(value) => Some(value),
+            None if removed || self.clear_environment => None,
+            None => std::env::var_os("PATH"),
+        }
+    }
+
+    /// Removes the environment variable `key` from the child's environment,
+    /// including values added earlier.
+    pub(crate) fn remove_environment_variable(&mut self, key: &OsStr) {
+        let variables = std::mem::take(&mut self.added_environment_variables);
+        let secrets = std::mem::take(&mut self.secret_environment_variables);
+        for (index, (added_key, value)) in variables.into_iter().enumerate() {
+            if added_key != key {
+                if secrets.contains(&index) {
+                    self.secret_environment_variables
+                        .insert(self.added_environment_variables.len());
+                }
+                self.added_environment_variables.push((added_key, value));
+            }
+        }
+        self.removed_environment_variables
+            .insert(key.to_os_string());
+    }
+
+    /// Removes all environment variables from the child's environment,
+    /// including the inherited ones and values added earlier.
+    pub(crate) fn clear_environment_variables(&mut self) {
+        self.added_environment_variables.clear();
+        self.secret_environment_variables.clear();
+        self.removed_environment_variables.clear();
+        self.clear_environment = true;
+    }
+
+    pub(crate) fn configure_environment(&self, command: &mut Command) {
+        if self.clear_environment {
+            command.env_clear();
+        }
+        for key in &self.removed_environment_variables {
+            command.env_remove(key);
+        }
+        for (key, value) in &self.added_environment_variables {
+            command.env(key, value);
+        }
+    }
+}
+
+/// Builds a [`Command`] with the arguments, environment variables and
+/// working directory of the [`Config`].
+/// Fails with [`Error::NoExecutableGiven`] for configs without arguments.
+///
+/// Other settings, like `stdin` or [`LogCommand`](crate::input::LogCommand),
+/// don't have an equivalent in [`Command`] and are dropped.
+/// See also [`Input::into_command`](crate::input::Input::into_command).
+impl TryFrom<Config> for Command {
+    type Error = Error;
+
+    fn try_from(config: Config) -> Result<Command, Error> {
+        if let Some(error) = &config.configuration_error {
+            return Err(Error::from_configuration_error(&config, error));
+        }
+        let mut arguments = config.arguments.iter();
+        let mut command = match arguments.next() {
+            Some(executable) => Command::new(executable),
+            None => return Err(Error::NoExecutableGiven),
+        };
+        command.args(arguments);
+        config.configure_environment(&mut command);
+        if let Some(working_directory) = &config.working_directory {
+            command.current_dir(working_directory);
+        }
+        Ok(command)
+    }
+}
+
+#[cfg(unix)]
+fn is_path_variable(key: &OsStr) -> bool {
+    key == "PATH"
+}
+
+#[cfg(not(unix))]
+fn is_path_variable(key: &OsStr) -> bool {
+    key.to_string_lossy().eq_ignore_ascii_case("PATH")
+}
+
+pub(crate) const REDACTED: &str = "***";
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Destructuring makes sure that new fields are not forgotten here.
+        let Config {
+            arguments: _,
+            secret_arguments: _,
+            log_command,
+            working_directory,
+            added_environment_variables: _,
+            secret_environment_variables: _,
+            removed_environment_variables,
+            clear_environment,
+            configuration_error,
+            stdin,
+            secret_stdin,
+            inherit_stdin,
+            inherit_stdout,
+            inherit_stderr,
+            capture_stdout,
+            capture_stderr,
+            error_on_non_zero_exit_code,
+            kill_on_drop,
+            keep_stdin_open,
+            stream_stdout,
+            #[cfg(unix)]
+            process_group,
+            #[cfg(unix)]
+            graceful_stop,
+            #[cfg(unix)]
+            pty,
+            #[cfg(unix)]
+            pty_size,
+        } = self;
+        let mut debug = f.debug_struct("Config");
+        debug
+            .field("arguments", &self.redacted_arguments())
+            .field("log_command", log_command)
+            .field("working_directory", working_directory)
+            .field(
+                "added_environment_variables",
+                &self.redacted_environment_variables(),
+            )
+            .field(
+                "removed_environment_variables",
+                removed_environment_variables,
+            )
+            .field("clear_environment", clear_environment)
+            .field("configuration_error", configuration_error);
+        if *secret_stdin {
+            debug.field("stdin", &stdin.as_ref().map(|_| REDACTED));
+        } else {
+            debug.field("stdin", stdin);
+        }
+        debug
+            .field("inherit_stdin", inherit_stdin)
+            .field("inherit_stdout", inherit_stdout)
+            .field("inherit_stderr", inherit_stderr)
+            .field("capture_stdout", capture_stdout)
+            .field("capture_stderr", capture_stderr)
+            .field("error_on_non_zero_exit_code", error_on_non_zero_exit_code)
+            .field("kill_on_drop", kill_on_drop)
+            .field("keep_stdin_open", keep_stdin_open)
+            .field("stream_stdout", stream_stdout);
+        #[cfg(unix)]
+        debug
+            .field("process_group", process_group)
+            .field("graceful_stop", graceful_stop)
+            .field("pty", pty)
+            .field("pty_size", pty_size);
+        debug.finish()
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             arguments: Vec::new(),
+            secret_arguments: BTreeSet::new(),
             log_command: false,
             working_directory: None,
             added_environment_variables: Vec::new(),
+            secret_environment_variables: BTreeSet::new(),
+            removed_environment_variables: BTreeSet::new(),
+            clear_environment: false,
+            configuration_error: None,
             stdin: None,
+            secret_stdin: false,
+            inherit_stdin: false,
+            inherit_stdout: false,
+            inherit_stderr: false,
             capture_stdout: false,
             capture_stderr: false,
             error_on_non_zero_exit_code: true,
+            kill_on_drop: true,
+            keep_stdin_open: false,
+            stream_stdout: false,
+            #[cfg(unix)]
+            process_group: ProcessGroup::Inherited,
+            #[cfg(unix)]
+            graceful_stop: GracefulStop::default(),
+            #[cfg(unix)]
+            pty: false,
+            #[cfg(unix)]
+            pty_size: None,
         }
     }
 }
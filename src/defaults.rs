@@ -0,0 +1,180 @@
+//! Default inputs that are used for all child processes,
+//! either process-wide or in a scope.
+//!
+//! Build scripts often pass the same inputs to every command,
+//! e.g. [`LogCommand`](crate::input::LogCommand) or
+//! [`CurrentDir`](crate::input::CurrentDir).
+//! Instead, they can be set as defaults:
+//!
+//! ```
+//! use cradle::prelude::*;
+//!
+//! with_defaults((LogCommand, Env("LC_ALL", "C")), || {
+//!     // writes "+ sh -c 'echo $LC_ALL'" to stderr
+//!     let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $LC_ALL");
+//!     assert_eq!(output, "C");
+//! });
+//! ```
+//!
+//! Defaults are applied before the inputs of each call,
+//! so inputs given to [`run!`] and friends can override them,
+//! e.g. a later [`Env`](crate::input::Env) with the same key wins,
+//! and a later [`CurrentDir`](crate::input::CurrentDir) replaces the default one.
+//! Defaults that add arguments, like `"sudo"`, are prepended to the command.
+//!
+//! Defaults apply to [`run!`], [`run_output!`], [`run_result!`], [`spawn!`] and
+//! `session!`, and the corresponding methods on [`Input`].
+
+use crate::{config::Config, input::Input};
+use lazy_static::lazy_static;
+use std::{
+    cell::RefCell,
+    marker::PhantomData,
+    rc::Rc,
+    sync::{Mutex, MutexGuard},
+};
+
+lazy_static! {
+    static ref GLOBAL_DEFAULTS: Mutex<Option<Config>> = Mutex::new(None);
+}
+
+/// A scoped default input, that is applied to every child process.
+type ScopedInput = Rc<dyn Fn(&mut Config)>;
+
+thread_local! {
+    /// The scoped defaults of all [`DefaultsGuard`]s of the current thread,
+    /// the innermost last.
+    // `const` initializers for thread locals require rust 1.59.
+    #[allow(unknown_lints, clippy::missing_const_for_thread_local)]
+    static SCOPED_DEFAULTS: RefCell<Vec<ScopedInput>> = RefCell::new(Vec::new());
+}
+
+fn global_defaults() -> MutexGuard<'static, Option<Config>> {
+    GLOBAL_DEFAULTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns the [`Config`] that child processes start out with:
+/// the process-wide defaults, or [`Config::default`],
+/// with all scoped defaults applied on top, the innermost last.
+pub(crate) fn base_config() -> Config {
+    let mut config = global_defaults().clone().unwrap_or_default();
+    let scoped = SCOPED_DEFAULTS.with(|scoped| scoped.borrow().clone());
+    for input in scoped {
+        input(&mut config);
+    }
+    config
+}
+
+/// Sets default inputs for all child processes that are started afterwards,
+/// in all threads.
+/// Replaces previously set process-wide defaults.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// set_defaults(LogCommand);
+/// // writes '+ echo foo' to stderr
+/// run!(%"echo foo");
+/// # clear_defaults();
+/// ```
+///
+/// Scoped defaults (see [`with_defaults`]) take precedence over
+/// process-wide defaults.
+pub fn set_defaults(input: impl Input) {
+    let mut config = Config::default();
+    input.configure(&mut config);
+    *global_defaults() = Some(config);
+}
+
+/// Removes the defaults set with [`set_defaults`].
+pub fn clear_defaults() {
+    *global_defaults() = None;
+}
+
+/// Runs `f` with `input` as default input for all child processes
+/// started in the current thread:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # let dir = temp_dir.path().to_path_buf();
+/// use cradle::prelude::*;
+///
+/// with_defaults(CurrentDir(dir.clone()), || {
+///     run!(%"touch foo");
+///     run!(%"touch bar");
+/// });
+/// assert!(dir.join("foo").exists());
+/// assert!(dir.join("bar").exists());
+/// ```
+///
+/// The defaults are added on top of the process-wide defaults
+/// and the scoped defaults of enclosing scopes, so scopes can be nested.
+/// They don't apply to other threads, including threads spawned by `f`.
+/// See also [`scoped_defaults`].
+pub fn with_defaults<I, T, F>(input: I, f: F) -> T
+where
+    I: Input + Clone + 'static,
+    F: FnOnce() -> T,
+{
+    let _guard = scoped_defaults(input);
+    f()
+}
+
+/// Like [`with_defaults`], but the defaults stay in effect
+/// until the returned [`DefaultsGuard`] is dropped:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let guard = scoped_defaults(Env("FOO", "bar"));
+/// let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO");
+/// assert_eq!(output, "bar");
+/// drop(guard);
+/// let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO");
+/// assert_eq!(output, "");
+/// ```
+///
+/// Scoped defaults are applied anew for every child process,
+/// on top of the process-wide defaults that are in effect at that time.
+/// So `input` has to be [`Clone`] and can't borrow local variables.
+/// E.g. an [`EnvFile`](crate::input::EnvFile) is read for every child process.
+///
+/// Guards restore the defaults that were in effect when they were created.
+/// Dropping a guard also removes the defaults of all guards
+/// that were created after it in the same thread.
+pub fn scoped_defaults<I>(input: I) -> DefaultsGuard
+where
+    I: Input + Clone + 'static,
+{
+    let input: ScopedInput = Rc::new(move |config| input.clone().configure(config));
+    let depth = SCOPED_DEFAULTS.with(|scoped| {
+        let mut scoped = scoped.borrow_mut();
+        scoped.push(input);
+        scoped.len() - 1
+    });
+    DefaultsGuard {
+        depth,
+        not_send: PhantomData,
+    }
+}
+
+/// Restores the previous defaults when dropped.
+/// See [`scoped_defaults`].
+#[must_use = "the defaults are removed when the guard is dropped"]
+#[derive(Debug)]
+pub struct DefaultsGuard {
+    /// The number of scoped defaults that were in effect
+    /// when the guard was created.
+    depth: usize,
+    /// Scoped defaults are thread-local, so the guard must not be
+    /// dropped in another thread.
+    not_send: PhantomData<*const ()>,
+}
+
+impl Drop for DefaultsGuard {
+    fn drop(&mut self) {
+        SCOPED_DEFAULTS.with(|scoped| scoped.borrow_mut().truncate(self.depth));
+    }
+}
@@ -0,0 +1,293 @@
+//! An internal module for parsing `.env` files, see [`EnvFile`](crate::input::EnvFile).
+
+use std::ffi::OsString;
+
+/// A parse error in line `line` (1-based) of an `.env` file.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParseError {
+    pub(crate) line: usize,
+    pub(crate) message: String,
+}
+
+/// Parses the contents of an `.env` file into key-value pairs, in order.
+///
+/// `${VAR}` references in values are looked up in the variables
+/// defined earlier in the file first, then with `lookup`.
+/// Undefined variables expand to the empty string.
+pub(crate) fn parse(
+    content: &str,
+    lookup: impl Fn(&str) -> Option<OsString>,
+) -> Result<Vec<(OsString, OsString)>, ParseError> {
+    let mut result: Vec<(OsString, OsString)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let error = |message: &str| ParseError {
+            line: index + 1,
+            message: message.to_string(),
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = match strip_prefix(line, "export") {
+            Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+            _ => line,
+        };
+        let (key, value) = match line.find('=') {
+            Some(index) => (line[..index].trim_end(), line[index + 1..].trim_start()),
+            None => return Err(error("expected KEY=VALUE")),
+        };
+        if !is_valid_key(key) {
+            return Err(error(&format!("invalid variable name {:?}", key)));
+        }
+        let lookup = |name: &str| {
+            result
+                .iter()
+                .rev()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| lookup(name))
+                .unwrap_or_default()
+        };
+        let value = parse_value(value, lookup).map_err(|message| error(&message))?;
+        result.push((OsString::from(key), value));
+    }
+    Ok(result)
+}
+
+/// `str::strip_prefix` requires rust 1.45.
+#[rustversion::attr(since(1.48), allow(clippy::manual_strip))]
+fn strip_prefix<'a>(string: &'a str, prefix: &str) -> Option<&'a str> {
+    if string.starts_with(prefix) {
+        Some(&string[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '.')
+        }
+        _ => false,
+    }
+}
+
+fn parse_value(value: &str, lookup: impl Fn(&str) -> OsString) -> Result<OsString, String> {
+    let (value, rest) = if let Some(quoted) = strip_prefix(value, "'") {
+        match quoted.find('\'') {
+            Some(end) => (OsString::from(&quoted[..end]), &quoted[end + 1..]),
+            None => return Err("unterminated single quote".to_string()),
+        }
+    } else if let Some(quoted) = strip_prefix(value, "\"") {
+        parse_double_quoted(quoted, lookup)?
+    } else {
+        let end = value
+            .char_indices()
+            .find(|(index, char)| *char == '#' && value[..*index].ends_with(char::is_whitespace))
+            .map(|(index, _)| index)
+            .unwrap_or_else(|| value.len());
+        (expand(value[..end].trim_end(), lookup)?, "")
+    };
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!(
+            "unexpected characters after quoted value: {:?}",
+            rest
+        ));
+    }
+    Ok(value)
+}
+
+/// Parses the part of a double-quoted value after the opening quote.
+/// Returns the value and what follows the closing quote.
+fn parse_double_quoted(
+    quoted: &str,
+    lookup: impl Fn(&str) -> OsString,
+) -> Result<(OsString, &str), String> {
+    let mut unescaped = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, char)) = chars.next() {
+        match char {
+            '"' => return Ok((expand(&unescaped, lookup)?, &quoted[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => unescaped.push('\n'),
+                Some((_, 't')) => unescaped.push('\t'),
+                Some((_, 'r')) => unescaped.push('\r'),
+                // Escaped dollar signs are protected from expansion
+                // by doubling them.
+                Some((_, '$')) => unescaped.push_str("$$"),
+                Some((_, escaped)) => unescaped.push(escaped),
+                None => break,
+            },
+            '$' => unescaped.push('$'),
+            char => unescaped.push(char),
+        }
+    }
+    Err("unterminated double quote".to_string())
+}
+
+/// Expands `${VAR}` references. `$$` is a literal `$`.
+fn expand(value: &str, lookup: impl Fn(&str) -> OsString) -> Result<OsString, String> {
+    let mut result = OsString::new();
+    let mut rest = value;
+    while let Some(index) = rest.find('$') {
+        result.push(&rest[..index]);
+        let after = &rest[index + 1..];
+        if let Some(after) = strip_prefix(after, "$") {
+            result.push("$");
+            rest = after;
+        } else if let Some(reference) = strip_prefix(after, "{") {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| "unterminated variable reference".to_string())?;
+            let name = &reference[..end];
+            if !is_valid_key(name) {
+                return Err(format!("invalid variable name {:?}", name));
+            }
+            result.push(lookup(name));
+            rest = &reference[end + 1..];
+        } else {
+            result.push("$");
+            rest = after;
+        }
+    }
+    result.push(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse_to_strings(content: &str) -> Result<Vec<(String, String)>, ParseError> {
+        let lookup = |name: &str| match name {
+            "OUTER" => Some(OsString::from("outer")),
+            _ => None,
+        };
+        Ok(parse(content, lookup)?
+            .into_iter()
+            .map(|(key, value)| (key.into_string().unwrap(), value.into_string().unwrap()))
+            .collect())
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn simple_assignments() {
+        assert_eq!(
+            parse_to_strings("FOO=foo\nBAR = bar baz \n").unwrap(),
+            pairs(&[("FOO", "foo"), ("BAR", "bar baz")])
+        );
+    }
+
+    #[test]
+    fn comments_and_empty_lines() {
+        assert_eq!(
+            parse_to_strings("# comment\n\n  # indented\nFOO=foo # trailing\nBAR=a#b\n").unwrap(),
+            pairs(&[("FOO", "foo"), ("BAR", "a#b")])
+        );
+    }
+
+    #[test]
+    fn export_prefix() {
+        assert_eq!(
+            parse_to_strings("export FOO=foo\nexporter=bar\n").unwrap(),
+            pairs(&[("FOO", "foo"), ("exporter", "bar")])
+        );
+    }
+
+    #[test]
+    fn empty_values() {
+        assert_eq!(
+            parse_to_strings("FOO=\nBAR=''\n").unwrap(),
+            pairs(&[("FOO", ""), ("BAR", "")])
+        );
+    }
+
+    #[test]
+    fn single_quoted_values_are_literal() {
+        assert_eq!(
+            parse_to_strings("FOO=' a # ${OUTER} \\n' # comment\n").unwrap(),
+            pairs(&[("FOO", " a # ${OUTER} \\n")])
+        );
+    }
+
+    #[test]
+    fn double_quoted_values() {
+        assert_eq!(
+            parse_to_strings(r#"FOO=" a\n\"b\" \$ ${OUTER} # c""#).unwrap(),
+            pairs(&[("FOO", " a\n\"b\" $ outer # c")])
+        );
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(
+            parse_to_strings("FOO=foo\nBAR=${FOO}-${OUTER}-${UNDEFINED}\nFOO=${FOO}2\n").unwrap(),
+            pairs(&[("FOO", "foo"), ("BAR", "foo-outer-"), ("FOO", "foo2")])
+        );
+    }
+
+    #[test]
+    fn lone_dollar_signs_are_literal() {
+        assert_eq!(
+            parse_to_strings("FOO=$5 $FOO\n").unwrap(),
+            pairs(&[("FOO", "$5 $FOO")])
+        );
+    }
+
+    #[test]
+    fn errors_include_line_numbers() {
+        assert_eq!(
+            parse_to_strings("FOO=foo\n\nBAR\n"),
+            Err(ParseError {
+                line: 3,
+                message: "expected KEY=VALUE".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_keys() {
+        assert_eq!(
+            parse_to_strings("1FOO=foo").unwrap_err().message,
+            "invalid variable name \"1FOO\""
+        );
+    }
+
+    #[test]
+    fn unterminated_quotes() {
+        assert_eq!(
+            parse_to_strings("FOO='foo").unwrap_err().message,
+            "unterminated single quote"
+        );
+        assert_eq!(
+            parse_to_strings("FOO=\"foo").unwrap_err().message,
+            "unterminated double quote"
+        );
+    }
+
+    #[test]
+    fn characters_after_quoted_values() {
+        assert_eq!(
+            parse_to_strings("FOO='foo' bar").unwrap_err().message,
+            "unexpected characters after quoted value: \"bar\""
+        );
+    }
+
+    #[test]
+    fn unterminated_references() {
+        assert_eq!(
+            parse_to_strings("FOO=${BAR").unwrap_err().message,
+            "unterminated variable reference"
+        );
+    }
+}
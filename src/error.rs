@@ -1,7 +1,18 @@
 //! The [`Error`] type used in the return type of [`run_result!`].
 
-use crate::config::Config;
-use std::{ffi::OsString, fmt::Display, io, process::ExitStatus, string::FromUtf8Error};
+use crate::{
+    config::{Config, ConfigurationError, REDACTED},
+    output::Exit,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    io,
+    path::PathBuf,
+    process::ExitStatus,
+    string::FromUtf8Error,
+    sync::Arc,
+};
 
 /// Error type returned when an error occurs while using [`run_result!`]
 /// or [`crate::input::Input::run_result`].
@@ -37,18 +48,14 @@ pub enum Error {
     /// }
     /// ```
     ///
-    /// Note that this error doesn't necessarily mean that the executable file
-    /// could not be found.
-    /// A few other circumstances in which this can occur are:
-    ///
-    /// - a binary is dynamically linked against a library,
-    ///   but that library cannot be found, or
-    /// - the executable starts with a
-    ///   [shebang](https://en.wikipedia.org/wiki/Shebang_(Unix)),
-    ///   but the interpreter specified in the shebang cannot be found.
+    /// Before spawning, `cradle` resolves executables against the directories
+    /// in `PATH` (see [`Which`](crate::which::Which)).
+    /// `reason` tells why the executable couldn't be run.
+    /// See [`NotFoundReason`] for details.
     FileNotFound {
         executable: OsString,
         source: io::Error,
+        reason: NotFoundReason,
     },
     /// An IO error during execution. A few circumstances in which this can occur are:
     ///
@@ -72,9 +79,23 @@ pub enum Error {
     /// ```
     ///
     /// This error will be suppressed when [`Status`](crate::Status) is used.
+    ///
+    /// `exit` contains the same information as `exit_status`, but can be
+    /// matched on without using platform-specific extension traits:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<(), cradle::Error> = run_result!("false");
+    /// match result {
+    ///   Err(Error::NonZeroExitCode { exit: Exit::Code(1), .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
     NonZeroExitCode {
         full_command: String,
         exit_status: ExitStatus,
+        exit: Exit,
     },
     /// The child process's `stdout` is being captured,
     /// (e.g. with [`StdoutUntrimmed`](crate::StdoutUntrimmed)),
@@ -84,6 +105,50 @@ pub enum Error {
         full_command: String,
         source: FromUtf8Error,
     },
+    /// The child process's `stdout` is being parsed
+    /// with [`Parsed`](crate::output::Parsed),
+    /// but parsing failed.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<Parsed<u64>, Error> = run_result!(%"echo foo");
+    /// match result {
+    ///   Err(Error::ParseStdout { stdout, .. }) => assert_eq!(stdout, "foo"),
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    ParseStdout {
+        full_command: String,
+        stdout: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The child process's `stdout` is being deserialized
+    /// with [`Json`](crate::output::Json) or [`JsonLines`](crate::output::JsonLines),
+    /// but it's not valid JSON or doesn't match the expected type.
+    /// `snippet` contains the part of the output where the error occurred.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// # #[cfg(feature = "serde")]
+    /// # {
+    /// let result: Result<Json<u32>, Error> = run_result!(%"echo foo");
+    /// match result {
+    ///   Err(Error::ParseJson { snippet, .. }) => assert_eq!(snippet, "foo"),
+    ///   _ => panic!(),
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// Only returned with the `serde` feature. The variant exists without it,
+    /// so that enabling the feature doesn't break exhaustive matches on [`Error`].
+    /// `source` is a [`serde_json::Error`](https://docs.rs/serde_json/1/serde_json/struct.Error.html).
+    ParseJson {
+        full_command: String,
+        snippet: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
     /// The child process's `stderr` is being captured,
     /// (with [`Stderr`](crate::Stderr)),
     /// but the process wrote bytes to its `stderr` that are not
@@ -92,6 +157,49 @@ pub enum Error {
         full_command: String,
         source: FromUtf8Error,
     },
+    /// [`Session::expect`](crate::session::Session::expect) didn't find
+    /// its pattern in the output of the child process within the given timeout.
+    /// `unmatched` contains the output that was searched.
+    ExpectTimeout {
+        full_command: String,
+        pattern: String,
+        unmatched: String,
+    },
+    /// The child process closed its `stdout` before
+    /// [`Session::expect`](crate::session::Session::expect) found its pattern.
+    /// `unmatched` contains the output that was searched.
+    ExpectEof {
+        full_command: String,
+        pattern: String,
+        unmatched: String,
+    },
+    /// The pattern given to [`Session::expect`](crate::session::Session::expect)
+    /// is not a valid regular expression.
+    ///
+    /// Only returned with the `session` feature. The variant exists without it,
+    /// so that enabling the feature doesn't break exhaustive matches on [`Error`].
+    /// `source` is a [`regex::Error`](https://docs.rs/regex/1/regex/enum.Error.html).
+    InvalidPattern {
+        pattern: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A directory given to [`PathPrepend`](crate::input::PathPrepend) or
+    /// [`PathAppend`](crate::input::PathAppend) can't be added to `PATH`,
+    /// because it contains the path separator (`:` on unix, `;` on windows).
+    InvalidPathDirectory {
+        full_command: String,
+        directory: PathBuf,
+    },
+    /// An [`EnvFile`](crate::input::EnvFile) could not be parsed.
+    /// `line` is the 1-based line number of the error in the file at `path`.
+    ///
+    /// Errors while reading the file result in [`Error::CommandIoError`].
+    ParseEnvFile {
+        full_command: String,
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
     /// This error is raised when an internal invariant of `cradle` is broken,
     /// and likely indicates a bug.
     Internal {
@@ -101,6 +209,28 @@ pub enum Error {
     },
 }
 
+/// Why an executable couldn't be found, see [`Error::FileNotFound`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotFoundReason {
+    /// The executable was not found in any of the `searched` directories in `PATH`.
+    NotInPath { searched: Vec<PathBuf> },
+    /// A file with the name of the executable was found in `PATH`,
+    /// but it's not executable.
+    NotExecutable { path: PathBuf },
+    /// The executable at `path` starts with a
+    /// [shebang](https://en.wikipedia.org/wiki/Shebang_(Unix)),
+    /// but the `interpreter` specified in the shebang cannot be found.
+    BadInterpreter {
+        path: PathBuf,
+        interpreter: OsString,
+    },
+    /// The operating system reported a `file not found` error,
+    /// for example because a given path to an executable doesn't exist,
+    /// or because a binary is dynamically linked against a library
+    /// that cannot be found.
+    Other,
+}
+
 impl Error {
     pub(crate) fn command_io_error(config: &Config, source: io::Error) -> Error {
         Error::CommandIoError {
@@ -109,6 +239,94 @@ impl Error {
         }
     }
 
+    /// Builds an [`Error::FileNotFound`], with paths that contain
+    /// [`Secret`](crate::input::Secret)s replaced by `***`.
+    /// That is the case when the executable is a secret,
+    /// or when it was searched for in a secret `PATH`.
+    pub(crate) fn file_not_found(
+        config: &Config,
+        executable: &OsStr,
+        source: io::Error,
+        reason: NotFoundReason,
+    ) -> Error {
+        let executable_is_secret = config.secret_arguments.contains(&0);
+        let path_is_secret = config.path_variable_is_secret();
+        let redact = |path: PathBuf, is_secret: bool| {
+            if is_secret {
+                PathBuf::from(REDACTED)
+            } else {
+                path
+            }
+        };
+        let reason = match reason {
+            NotFoundReason::NotInPath { searched } => NotFoundReason::NotInPath {
+                searched: searched
+                    .into_iter()
+                    .map(|directory| redact(directory, path_is_secret))
+                    .collect(),
+            },
+            NotFoundReason::NotExecutable { path } => NotFoundReason::NotExecutable {
+                path: redact(path, executable_is_secret || path_is_secret),
+            },
+            NotFoundReason::BadInterpreter { path, interpreter } => {
+                NotFoundReason::BadInterpreter {
+                    path: redact(path, executable_is_secret || path_is_secret),
+                    interpreter,
+                }
+            }
+            NotFoundReason::Other => NotFoundReason::Other,
+        };
+        Error::FileNotFound {
+            executable: if executable_is_secret {
+                OsString::from(REDACTED)
+            } else {
+                executable.to_os_string()
+            },
+            source,
+            reason,
+        }
+    }
+
+    /// Builds a [`Error::ParseJson`] with a snippet of `json`
+    /// around the position where `source` occurred.
+    #[cfg(feature = "serde")]
+    pub(crate) fn parse_json(config: &Config, json: &str, source: serde_json::Error) -> Error {
+        Error::ParseJson {
+            full_command: config.full_command(),
+            snippet: json_snippet(json, source.line(), source.column()),
+            source: Box::new(source),
+        }
+    }
+
+    pub(crate) fn from_configuration_error(config: &Config, error: &ConfigurationError) -> Error {
+        match error {
+            ConfigurationError::InvalidPathDirectory(directory) => Error::InvalidPathDirectory {
+                full_command: config.full_command(),
+                directory: directory.clone(),
+            },
+            ConfigurationError::ReadEnvFile { path, source } => Error::command_io_error(
+                config,
+                io::Error::new(
+                    source.kind(),
+                    ReadEnvFileError {
+                        path: path.clone(),
+                        source: source.clone(),
+                    },
+                ),
+            ),
+            ConfigurationError::ParseEnvFile {
+                path,
+                line,
+                message,
+            } => Error::ParseEnvFile {
+                full_command: config.full_command(),
+                path: path.clone(),
+                line: *line,
+                message: message.clone(),
+            },
+        }
+    }
+
     pub(crate) fn internal(message: &str, config: &Config) -> Error {
         Error::Internal {
             message: message.to_string(),
@@ -127,6 +345,43 @@ pub fn panic_on_error<T>(result: Result<T, Error>) -> T {
     }
 }
 
+/// Returns up to 61 characters of line `line` in `json`, around `column`.
+/// Both `line` and `column` are 1-based, as reported by `serde_json`.
+/// `column` counts bytes, not characters.
+#[cfg(feature = "serde")]
+fn json_snippet(json: &str, line: usize, column: usize) -> String {
+    const CONTEXT: usize = 30;
+    let line = json
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default()
+        .trim_end();
+    let mut index = column.saturating_sub(1).min(line.len());
+    while !line.is_char_boundary(index) {
+        index -= 1;
+    }
+    let start = line[..index]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT - 1)
+        .map(|(start, _)| start)
+        .unwrap_or(0);
+    let end = line[index..]
+        .char_indices()
+        .nth(CONTEXT + 1)
+        .map(|(end, _)| index + end)
+        .unwrap_or_else(|| line.len());
+    let mut result = String::new();
+    if start > 0 {
+        result.push_str("...");
+    }
+    result.push_str(&line[start..end]);
+    if end < line.len() {
+        result.push_str("...");
+    }
+    result
+}
+
 fn english_list(list: &[&str]) -> String {
     let mut result = String::new();
     for (i, word) in list.iter().enumerate() {
@@ -176,9 +431,34 @@ impl Display for Error {
         use Error::*;
         match self {
             NoExecutableGiven => write!(f, "no arguments given"),
-            FileNotFound { executable, .. } => {
+            FileNotFound {
+                executable, reason, ..
+            } => {
                 let executable = executable.to_string_lossy();
                 write!(f, "File not found error when executing '{}'", executable)?;
+                match reason {
+                    NotFoundReason::NotInPath { searched } => write!(
+                        f,
+                        "\n  not found in PATH, searched: {}",
+                        searched
+                            .iter()
+                            .map(|directory| format!("'{}'", directory.display()))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )?,
+                    NotFoundReason::NotExecutable { path } => write!(
+                        f,
+                        "\n  found '{}' in PATH, but it's not executable",
+                        path.display()
+                    )?,
+                    NotFoundReason::BadInterpreter { path, interpreter } => write!(
+                        f,
+                        "\n  interpreter '{}' in the shebang of '{}' not found",
+                        interpreter.to_string_lossy(),
+                        path.display()
+                    )?,
+                    NotFoundReason::Other => {}
+                }
                 if let Some(whitespace_note) = executable_with_whitespace_note(executable.as_ref())
                 {
                     write!(f, "\n{}", whitespace_note)?;
@@ -187,27 +467,84 @@ impl Display for Error {
             }
             CommandIoError { message, .. } => write!(f, "{}", message),
             NonZeroExitCode {
-                full_command,
-                exit_status,
-            } => {
-                if let Some(exit_code) = exit_status.code() {
-                    write!(
-                        f,
-                        "{}:\n  exited with exit code: {}",
-                        full_command, exit_code
-                    )
-                } else {
-                    write!(f, "{}:\n  exited with {}", full_command, exit_status)
-                }
-            }
+                full_command, exit, ..
+            } => match exit {
+                Exit::Code(exit_code) => write!(
+                    f,
+                    "{}:\n  exited with exit code: {}",
+                    full_command, exit_code
+                ),
+                Exit::Signal { .. } => write!(f, "{}:\n  {}", full_command, exit),
+            },
             InvalidUtf8ToStdout { full_command, .. } => {
                 write!(f, "{}:\n  invalid utf-8 written to stdout", full_command)
             }
             InvalidUtf8ToStderr { full_command, .. } => {
                 write!(f, "{}:\n  invalid utf-8 written to stderr", full_command)
             }
+            ParseJson {
+                full_command,
+                snippet,
+                source,
+            } => write!(
+                f,
+                "{}:\n  could not parse stdout as json: {}\n  {}",
+                full_command, source, snippet
+            ),
+            ParseEnvFile {
+                full_command,
+                path,
+                line,
+                message,
+            } => write!(
+                f,
+                "{}:\n  could not parse env file '{}', line {}: {}",
+                full_command,
+                path.display(),
+                line,
+                message
+            ),
+            InvalidPathDirectory {
+                full_command,
+                directory,
+            } => write!(
+                f,
+                "{}:\n  can't add '{}' to PATH, because it contains the path separator",
+                full_command,
+                directory.display()
+            ),
+            ParseStdout {
+                full_command,
+                stdout,
+                source,
+            } => write!(
+                f,
+                "{}:\n  could not parse stdout {:?}: {}",
+                full_command, stdout, source
+            ),
+            ExpectTimeout {
+                full_command,
+                pattern,
+                unmatched,
+            } => write!(
+                f,
+                "{}:\n  timed out waiting for pattern {:?}, unmatched output: {:?}",
+                full_command, pattern, unmatched
+            ),
+            ExpectEof {
+                full_command,
+                pattern,
+                unmatched,
+            } => write!(
+                f,
+                "{}:\n  stdout closed before pattern {:?} matched, unmatched output: {:?}",
+                full_command, pattern, unmatched
+            ),
+            InvalidPattern { pattern, source } => {
+                write!(f, "invalid pattern {:?}: {}", pattern, source)
+            }
             Internal { .. } => {
-                let snippets = vec![
+                let snippets = [
                     "Congratulations, you've found a bug in cradle! :/",
                     "Please, open an issue on https://github.com/soenkehahn/cradle/issues",
                     "with the following information:",
@@ -224,11 +561,47 @@ impl std::error::Error for Error {
         match self {
             FileNotFound { source, .. } | CommandIoError { source, .. } => Some(source),
             InvalidUtf8ToStdout { source, .. } | InvalidUtf8ToStderr { source, .. } => Some(source),
-            NoExecutableGiven | NonZeroExitCode { .. } | Internal { .. } => None,
+            ParseStdout { source, .. }
+            | ParseJson { source, .. }
+            | InvalidPattern { source, .. } => Some(source.as_ref()),
+            NoExecutableGiven
+            | NonZeroExitCode { .. }
+            | ExpectTimeout { .. }
+            | ExpectEof { .. }
+            | InvalidPathDirectory { .. }
+            | ParseEnvFile { .. }
+            | Internal { .. } => None,
         }
     }
 }
 
+/// The [`io::Error`] of an [`EnvFile`](crate::input::EnvFile) that couldn't be read,
+/// together with its path.
+/// It's shared with the [`Config`], which can't hold an [`io::Error`] directly,
+/// because it's cloned.
+#[derive(Debug)]
+struct ReadEnvFileError {
+    path: PathBuf,
+    source: Arc<io::Error>,
+}
+
+impl Display for ReadEnvFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "could not read env file '{}': {}",
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for ReadEnvFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,6 +626,55 @@ mod tests {
         assert!(std::error::Error::source(&result.unwrap_err()).is_some());
     }
 
+    #[cfg(feature = "serde")]
+    mod json_snippet {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn returns_short_lines_completely() {
+            assert_eq!(json_snippet("foo", 1, 1), "foo");
+        }
+
+        #[test]
+        fn returns_the_given_line() {
+            assert_eq!(json_snippet("foo\nbar\nbaz", 2, 1), "bar");
+        }
+
+        #[test]
+        fn shortens_long_lines_around_the_column() {
+            let line = format!("{}X{}", "a".repeat(100), "b".repeat(100));
+            assert_eq!(
+                json_snippet(&line, 1, 101),
+                format!("...{}X{}...", "a".repeat(30), "b".repeat(30))
+            );
+        }
+
+        #[test]
+        fn treats_columns_as_byte_offsets() {
+            let line = format!("{}X{}", "ä".repeat(100), "b".repeat(100));
+            assert_eq!(
+                json_snippet(&line, 1, 201),
+                format!("...{}X{}...", "ä".repeat(30), "b".repeat(30))
+            );
+        }
+
+        #[test]
+        fn handles_columns_inside_of_characters() {
+            let line = format!("{}X", "ä".repeat(100));
+            assert_eq!(
+                json_snippet(&line, 1, 200),
+                format!("...{}X", "ä".repeat(31))
+            );
+        }
+
+        #[test]
+        fn handles_columns_beyond_the_line() {
+            assert_eq!(json_snippet("foo", 1, 10), "foo");
+            assert_eq!(json_snippet("foo", 3, 1), "");
+        }
+    }
+
     mod english_list {
         use super::*;
         use pretty_assertions::assert_eq;
@@ -0,0 +1,233 @@
+//! The [`Handle`] type for interacting with running child processes.
+
+#[cfg(feature = "session")]
+use crate::collected_output::OpenStdin;
+use crate::{
+    child_guard::ChildGuard, child_output::ChildOutput, collected_output::Waiter, config::Config,
+    context::Context, error::Error, output::Output,
+};
+use std::{fmt, io::Write, marker::PhantomData, sync::mpsc::Receiver};
+
+/// Signals that can be sent to child processes with [`Handle::signal`],
+/// or used in [`GracefulStop`](crate::input::GracefulStop).
+///
+/// This is a re-export of
+/// [`nix::sys::signal::Signal`](https://docs.rs/nix/0.22/nix/sys/signal/enum.Signal.html).
+///
+/// Only available on unix.
+#[cfg(unix)]
+pub use nix::sys::signal::Signal;
+
+/// A handle to a running child process, as returned by [`spawn!`]
+/// and [`Input::spawn`](crate::input::Input::spawn).
+///
+/// The type parameter `O` determines which outputs are collected
+/// from the child process, in the same way as the return type of
+/// [`run_output!`] does.
+/// [`Handle::wait`] waits for the child process to exit and returns them:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let handle = spawn!(%"echo foo").unwrap();
+/// let StdoutTrimmed(output) = handle.wait().unwrap();
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// While the child is running, you can send it signals:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let handle = spawn!(%"sleep 1000").unwrap();
+/// handle.signal(Signal::SIGINT).unwrap();
+/// let Status(status) = handle.wait().unwrap();
+/// assert!(!status.success());
+/// # }
+/// ```
+///
+/// When a [`Handle`] is dropped without waiting for the child,
+/// the child is stopped, as configured by [`GracefulStop`](crate::input::GracefulStop).
+/// This can be switched off with [`KillOnDrop`](crate::input::KillOnDrop).
+pub struct Handle<O> {
+    config: Config,
+    child: ChildGuard,
+    waiter: Waiter,
+    output: PhantomData<fn() -> O>,
+}
+
+impl<O> fmt::Debug for Handle<O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("config", &self.config)
+            .field("child", &self.child)
+            .field("waiter", &self.waiter)
+            .finish()
+    }
+}
+
+impl<O> Handle<O>
+where
+    O: Output,
+{
+    pub(crate) fn spawn<Stdout, Stderr>(
+        context: Context<Stdout, Stderr>,
+        mut config: Config,
+    ) -> Result<Self, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        <O as Output>::configure(&mut config);
+        let (child, waiter) = ChildOutput::spawn_child_process(context, &config)?;
+        Ok(Handle {
+            config,
+            child,
+            waiter,
+            output: PhantomData,
+        })
+    }
+
+    #[cfg(feature = "session")]
+    pub(crate) fn take_open_stdin(&mut self) -> Option<OpenStdin> {
+        self.waiter.take_open_stdin()
+    }
+
+    pub(crate) fn take_stdout_chunks(&mut self) -> Option<Receiver<Vec<u8>>> {
+        self.waiter.take_stdout_chunks()
+    }
+
+    #[cfg(feature = "session")]
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns an iterator over the lines that the child process writes to `stdout`,
+    /// while it's running.
+    /// Lines are yielded as soon as the child has written them,
+    /// without their line terminators (`\n` or `\r\n`).
+    /// The iterator ends when the child closes its `stdout`.
+    ///
+    /// This requires [`StdoutStream`](crate::output::StdoutStream) to be part of the
+    /// output type of the [`Handle`]:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let mut handle: Handle<(StdoutStream, Status)> = spawn!("printf", "foo\nbar").unwrap();
+    /// let lines = handle
+    ///     .stdout_lines()
+    ///     .unwrap()
+    ///     .collect::<Result<Vec<String>, Error>>()
+    ///     .unwrap();
+    /// assert_eq!(lines, vec!["foo", "bar"]);
+    /// let (StdoutStream, Status(status)) = handle.wait().unwrap();
+    /// assert!(status.success());
+    /// ```
+    ///
+    /// Returns `None` if [`StdoutStream`](crate::output::StdoutStream) is not used,
+    /// or if the lines have been taken already.
+    pub fn stdout_lines(&mut self) -> Option<StdoutLineStream> {
+        self.take_stdout_chunks().map(|chunks| StdoutLineStream {
+            full_command: self.config.full_command(),
+            chunks,
+            buffer: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// Returns the OS-assigned process identifier of the child process.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Sends `signal` to the child process.
+    /// If the child was started with [`NewProcessGroup`](crate::input::NewProcessGroup)
+    /// or [`NewSession`](crate::input::NewSession),
+    /// the signal is sent to all processes in its process group.
+    ///
+    /// Only available on unix.
+    #[cfg(unix)]
+    pub fn signal(&self, signal: Signal) -> Result<(), Error> {
+        self.child
+            .signal(signal)
+            .map_err(|error| Error::command_io_error(&self.config, error))
+    }
+
+    /// Waits for the child process to exit and returns its outputs.
+    pub fn wait(self) -> Result<O, Error> {
+        let child_output =
+            ChildOutput::wait_for_child_process(&self.config, self.child, self.waiter)?;
+        O::from_child_output(&self.config, &child_output)
+    }
+
+    /// Stops the child process, as configured by
+    /// [`GracefulStop`](crate::input::GracefulStop),
+    /// and returns its outputs.
+    ///
+    /// Since the child is killed by a signal, this results in an
+    /// [`Error::NonZeroExitCode`], unless that is suppressed,
+    /// e.g. with [`Status`](crate::output::Status).
+    pub fn stop(mut self) -> Result<O, Error> {
+        self.child
+            .terminate()
+            .map_err(|error| Error::command_io_error(&self.config, error))?;
+        self.wait()
+    }
+}
+
+/// An iterator over the lines of a child process's `stdout`,
+/// as returned by [`Handle::stdout_lines`].
+///
+/// Lines that are not valid utf-8 result in
+/// [`Error::InvalidUtf8ToStdout`].
+#[derive(Debug)]
+pub struct StdoutLineStream {
+    full_command: String,
+    chunks: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl StdoutLineStream {
+    fn next_line(&mut self) -> Option<Vec<u8>> {
+        loop {
+            if let Some(index) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let rest = self.buffer.split_off(index + 1);
+                let mut line = std::mem::replace(&mut self.buffer, rest);
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Some(line);
+            }
+            if self.finished {
+                return if self.buffer.is_empty() {
+                    None
+                } else {
+                    Some(std::mem::take(&mut self.buffer))
+                };
+            }
+            match self.chunks.recv() {
+                Ok(chunk) => self.buffer.extend_from_slice(&chunk),
+                Err(_) => self.finished = true,
+            }
+        }
+    }
+}
+
+impl Iterator for StdoutLineStream {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.next_line()?;
+        Some(
+            String::from_utf8(line).map_err(|source| Error::InvalidUtf8ToStdout {
+                full_command: self.full_command.clone(),
+                source,
+            }),
+        )
+    }
+}
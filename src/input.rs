@@ -1,14 +1,25 @@
 //! The [`Input`] trait that defines all possible inputs to a child process.
 
+#[cfg(feature = "session")]
+use crate::session::Session;
 use crate::{
     child_output::ChildOutput,
-    config::Config,
+    config::{Config, ConfigurationError},
     context::Context,
+    defaults, env_file,
     error::{panic_on_error, Error},
+    handle::Handle,
     output::Output,
 };
+#[cfg(unix)]
+use crate::{config::ProcessGroup, handle::Signal};
+#[cfg(unix)]
+use std::time::Duration;
 use std::{
+    collections::{BTreeSet, LinkedList, VecDeque},
+    convert::TryFrom,
     ffi::{OsStr, OsString},
+    fmt::{self, Display},
     io::Write,
     path::{Path, PathBuf},
     sync::Arc,
@@ -34,12 +45,20 @@ use std::{
 /// Here's a non-exhaustive list of the most commonly used types to get you started:
 ///
 /// - [`String`] and [`&str`],
+/// - numbers (except [`u8`]), [`char`]s and [`Arg`] for other types that implement [`Display`],
 /// - [`Split`] (and its shortcut `%`) to split commands by whitespace,
 /// - [`PathBuf`] and [`&Path`],
 /// - multiple sequence types, like [`vectors`], [`slices`] and (since version 1.51) [`arrays`],
+/// - [`Args`] for iterators,
+/// - [`Cmd`] for reusable commands,
+/// - [`Flag`], [`FlagEq`] and [`Repeat`] for options with values,
 /// - [`CurrentDir`],
-/// - [`Env`] for setting environment variables,
-/// - [`Stdin`], and
+/// - [`Env`] and [`EnvFile`] for setting environment variables,
+/// - [`PathPrepend`] and [`PathAppend`] for modifying `PATH`,
+/// - [`Stdin`] and [`StdinNulSeparated`],
+/// - [`Option`] and [`When`] for conditional arguments,
+/// - [`Command`](std::process::Command) (since version 1.57),
+/// - [`Inherit`] for running interactive commands, and
 /// - [`LogCommand`].
 ///
 /// [`String`]: trait.Input.html#impl-Input-for-String
@@ -151,6 +170,29 @@ use std::{
 /// as demonstrated in the code snippet above.
 /// [`Config`]'s fields are private to allow to add new features to `cradle`
 /// without introducing breaking API changes.
+///
+/// For structs that describe command line options, you can also
+/// enable the `macros` feature and use `#[derive(Input)]`:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(feature = "macros")]
+/// # {
+/// #[derive(Input)]
+/// struct Options {
+///     verbose: bool,
+///     name: Option<String>,
+/// }
+///
+/// let options = Options {
+///     verbose: true,
+///     name: Some("foo".to_string()),
+/// };
+/// let StdoutTrimmed(output) = run_output!("echo", options);
+/// assert_eq!(output, "--verbose --name foo");
+/// # }
+/// ```
 pub trait Input: Sized {
     /// Configures the given [`Config`](crate::config::Config) for the [`Input`] `self`.
     /// Usually you won't have to write your own custom impls for [`Input`],
@@ -214,6 +256,103 @@ pub trait Input: Sized {
         let context = Context::production();
         run_result_with_context(context, self)
     }
+
+    /// `input.spawn()` starts `input` as a child process,
+    /// without waiting for it to finish.
+    /// It's equivalent to `spawn!(input)`.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let handle = ("echo", "foo").spawn().unwrap();
+    /// let StdoutTrimmed(output) = handle.wait().unwrap();
+    /// assert_eq!(output, "foo");
+    /// ```
+    ///
+    /// See [`Handle`] for more details.
+    fn spawn<O>(self) -> Result<Handle<O>, crate::error::Error>
+    where
+        O: Output,
+    {
+        let context = Context::production();
+        spawn_with_context(context, self)
+    }
+
+    /// `input.session()` starts `input` as a child process
+    /// for scripted interaction.
+    /// It's equivalent to `session!(input)`.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut session = ("cat",).session().unwrap();
+    /// session.send_line("foo").unwrap();
+    /// session.expect("foo", Duration::from_secs(5)).unwrap();
+    /// ```
+    ///
+    /// See [`Session`] for more details.
+    ///
+    /// Only available with the `session` feature.
+    #[cfg(feature = "session")]
+    fn session(self) -> Result<Session, crate::error::Error> {
+        let context = Context::production();
+        session_with_context(context, self)
+    }
+
+    /// `input.into_command()` builds a [`std::process::Command`]
+    /// with the arguments, environment variables and working directory
+    /// of `input`, without running it.
+    /// This is useful for handing commands to APIs that expect a
+    /// [`std::process::Command`].
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let mut command = (Split("echo foo"), CurrentDir("/")).into_command().unwrap();
+    /// let output = command.output().unwrap();
+    /// assert_eq!(output.stdout, b"foo\n");
+    /// ```
+    ///
+    /// Defaults (see [`set_defaults`](crate::defaults::set_defaults))
+    /// are applied.
+    /// Settings that [`std::process::Command`] has no equivalent for,
+    /// like [`Stdin`] or [`LogCommand`], are dropped.
+    fn into_command(self) -> Result<std::process::Command, crate::error::Error> {
+        let mut config = defaults::base_config();
+        self.configure(&mut config);
+        std::process::Command::try_from(config)
+    }
+}
+
+#[cfg(feature = "session")]
+pub(crate) fn session_with_context<Stdout, Stderr, I>(
+    context: Context<Stdout, Stderr>,
+    input: I,
+) -> Result<Session, Error>
+where
+    Stdout: Write + Clone + Send + 'static,
+    Stderr: Write + Clone + Send + 'static,
+    I: Input,
+{
+    let mut config = defaults::base_config();
+    input.configure(&mut config);
+    Session::spawn(context, config)
+}
+
+pub(crate) fn spawn_with_context<Stdout, Stderr, I, O>(
+    context: Context<Stdout, Stderr>,
+    input: I,
+) -> Result<Handle<O>, Error>
+where
+    Stdout: Write + Clone + Send + 'static,
+    Stderr: Write + Clone + Send + 'static,
+    I: Input,
+    O: Output,
+{
+    let mut config = defaults::base_config();
+    input.configure(&mut config);
+    Handle::spawn(context, config)
 }
 
 pub(crate) fn run_result_with_context<Stdout, Stderr, I, O>(
@@ -226,7 +365,7 @@ where
     I: Input,
     O: Output,
 {
-    let mut config = Config::default();
+    let mut config = defaults::base_config();
     input.configure(&mut config);
     ChildOutput::run_child_process_output(context, config)
 }
@@ -321,6 +460,75 @@ impl Input for String {
     }
 }
 
+macro_rules! display_impl {
+    ($($type:ty,)+) => {
+        $(
+            /// Numbers are passed to the child process as arguments,
+            /// formatted with their [`Display`] implementation.
+            /// See [`Arg`] for an example.
+            impl Input for $type {
+                #[doc(hidden)]
+                fn configure(self, config: &mut Config) {
+                    Arg(self).configure(config);
+                }
+            }
+        )+
+    };
+}
+
+// `u8` is left out, so that byte vectors and slices like `Vec<u8>` and `&[u8]`
+// don't silently turn into lists of decimal arguments.
+// Single bytes can still be passed in with `Arg`.
+display_impl!(i8, i16, i32, i64, i128, isize, u16, u32, u64, u128, usize, f32, f64,);
+
+/// Arguments of type [`char`] are passed to the child process
+/// as arguments.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(%"cut -d", ':', %"-f 2", Stdin("foo:bar"));
+/// assert_eq!(output, "bar");
+/// ```
+impl Input for char {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        Arg(self).configure(config);
+    }
+}
+
+/// Passes the given value to the child process as a single argument,
+/// formatted with its [`Display`] implementation.
+/// Numbers and [`char`]s can be used directly as arguments,
+/// [`Arg`] allows to do the same for all other types that implement [`Display`]:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::net::Ipv4Addr;
+///
+/// let lines = 1;
+/// let StdoutTrimmed(output) = run_output!("head", "-n", lines, Stdin("foo\nbar\n"));
+/// assert_eq!(output, "foo");
+/// let StdoutTrimmed(output) = run_output!("echo", Arg(Ipv4Addr::LOCALHOST));
+/// assert_eq!(output, "127.0.0.1");
+/// ```
+///
+/// The exception is [`u8`], which is not an [`Input`],
+/// so that `Vec<u8>` and `&[u8]` can't be passed in as arguments by accident.
+/// Use `Arg(byte)` instead.
+#[derive(Debug, Clone)]
+pub struct Arg<T: Display>(pub T);
+
+impl<T> Input for Arg<T>
+where
+    T: Display,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        self.0.to_string().configure(config);
+    }
+}
+
 /// Splits the contained string by whitespace (using [`split_whitespace`])
 /// and uses the resulting words as separate arguments.
 ///
@@ -522,154 +730,1166 @@ where
     }
 }
 
-/// Passing in [`LogCommand`] as an argument to `cradle` will cause it
-/// to log the commands (including all arguments) to `stderr`.
-/// (This is similar `bash`'s `-x` option.)
+macro_rules! collection_impl {
+    ($($collection:ident,)+) => {
+        $(
+            /// Similar to the implementation for [`Vec<T>`].
+            /// All elements of the collection will be used as arguments,
+            /// in iteration order.
+            impl<T> Input for $collection<T>
+            where
+                T: Input,
+            {
+                #[doc(hidden)]
+                fn configure(self, config: &mut Config) {
+                    Args(self).configure(config);
+                }
+            }
+        )+
+    };
+}
+
+collection_impl!(VecDeque, LinkedList, BTreeSet,);
+
+/// Uses all items of the given iterator as inputs,
+/// in the order they are yielded.
+/// This allows to pass in iterators without collecting them into a [`Vec`] first:
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// run!(LogCommand, %"echo foo");
-/// // writes '+ echo foo' to stderr
+/// let files = vec!["foo.txt", "bar.rs", "baz.txt"];
+/// let StdoutTrimmed(output) = run_output!(
+///     "echo",
+///     Args(files.iter().filter(|file| file.ends_with(".txt")))
+/// );
+/// assert_eq!(output, "foo.txt baz.txt");
 /// ```
-#[derive(Debug, Clone, Copy)]
-pub struct LogCommand;
+///
+/// The items can be any [`Input`], not only arguments.
+///
+/// [`HashSet`](std::collections::HashSet)s don't implement [`Input`],
+/// since their iteration order is random.
+/// If the order doesn't matter, they can be wrapped in [`Args`].
+#[derive(Debug, Clone)]
+pub struct Args<I>(pub I)
+where
+    I: IntoIterator,
+    I::Item: Input;
 
-impl Input for LogCommand {
+impl<I> Input for Args<I>
+where
+    I: IntoIterator,
+    I::Item: Input,
+{
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        config.log_command = true;
+        for item in self.0 {
+            item.configure(config);
+        }
     }
 }
 
-/// By default child processes inherit the current directory from their
-/// parent. You can override this with [`CurrentDir`]:
+/// [`Some`] values are used as inputs, [`None`] contributes nothing.
+/// This allows to add arguments conditionally:
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// # #[cfg(target_os = "linux")]
-/// # {
-/// let StdoutTrimmed(output) = run_output!("pwd", CurrentDir("/tmp"));
-/// assert_eq!(output, "/tmp");
-/// # }
+/// let verbose = false;
+/// let flag = if verbose { Some("--verbose") } else { None };
+/// let StdoutTrimmed(output) = run_output!(%"echo foo", flag);
+/// assert_eq!(output, "foo");
 /// ```
 ///
-/// Paths that are relative to the parent's current directory are allowed.
-#[derive(Debug, Clone)]
-pub struct CurrentDir<T: AsRef<Path>>(pub T);
-
-impl<T> Input for CurrentDir<T>
+/// See also [`When`].
+impl<T> Input for Option<T>
 where
-    T: AsRef<Path>,
+    T: Input,
 {
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        config.working_directory = Some(self.0.as_ref().to_owned());
+        if let Some(t) = self {
+            t.configure(config);
+        }
     }
 }
 
-/// Arguments of type [`PathBuf`] are passed to the child process
-/// as arguments.
+/// Uses the given input only if the condition is `true`.
+/// Otherwise it contributes nothing, like `None`:
 ///
 /// ```
 /// use cradle::prelude::*;
-/// use std::path::PathBuf;
 ///
-/// let current_dir: PathBuf = std::env::current_dir().unwrap();
-/// run!("ls", current_dir);
+/// let release = true;
+/// let StdoutTrimmed(output) = run_output!(%"echo cargo build", When(release, "--release"));
+/// assert_eq!(output, "cargo build --release");
 /// ```
-impl Input for PathBuf {
+///
+/// In contrast to [`bool::then`], the input is always constructed,
+/// which is shorter for inputs that are cheap to create, like string literals.
+#[derive(Debug, Clone)]
+pub struct When<T: Input>(pub bool, pub T);
+
+impl<T> Input for When<T>
+where
+    T: Input,
+{
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        self.into_os_string().configure(config);
+        let When(condition, input) = self;
+        if condition {
+            input.configure(config);
+        }
     }
 }
 
-/// Arguments of type [`&Path`] are passed to the child process
-/// as arguments.
+/// Passes a flag followed by its value as two separate arguments,
+/// e.g. `--out path`:
 ///
 /// ```
-/// # let temp_dir = tempfile::TempDir::new().unwrap();
-/// # std::env::set_current_dir(&temp_dir).unwrap();
 /// use cradle::prelude::*;
 /// use std::path::Path;
 ///
-/// let file: &Path = Path::new("./foo");
-/// run!("touch", file);
+/// let StdoutTrimmed(output) = run_output!("echo", Flag("-o", Path::new("foo.o")));
+/// assert_eq!(output, "-o foo.o");
 /// ```
 ///
-/// [`&Path`]: std::path::Path
-impl Input for &Path {
+/// The value can be any [`Input`], e.g. a number.
+/// See also [`FlagEq`] and [`Repeat`].
+#[derive(Debug, Clone)]
+pub struct Flag<Key, Value>(pub Key, pub Value)
+where
+    Key: Input,
+    Value: Input;
+
+impl<Key, Value> Input for Flag<Key, Value>
+where
+    Key: Input,
+    Value: Input,
+{
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        self.as_os_str().to_os_string().configure(config);
+        let Self(key, value) = self;
+        (key, value).configure(config);
     }
 }
 
-/// Writes the given byte slice to the child's standard input.
+/// Passes a flag and its value as a single argument, joined by `=`,
+/// e.g. `--out=path`:
 ///
 /// ```
 /// use cradle::prelude::*;
+/// use std::path::Path;
 ///
-/// # #[cfg(target_os = "linux")]
-/// # {
-/// let StdoutUntrimmed(output) = run_output!("sort", Stdin("foo\nbar\n"));
-/// assert_eq!(output, "bar\nfoo\n");
-/// # }
+/// let StdoutTrimmed(output) = run_output!("echo", FlagEq("--out", Path::new("foo.o")));
+/// assert_eq!(output, "--out=foo.o");
 /// ```
 ///
-/// If `Stdin` is used multiple times, all given bytes slices will be written
-/// to the child's standard input in order.
+/// The argument is built from [`OsStr`]s, so values that are not valid utf-8,
+/// like some paths, are passed through unchanged.
 #[derive(Debug, Clone)]
-pub struct Stdin<T: AsRef<[u8]>>(pub T);
+pub struct FlagEq<Key, Value>(pub Key, pub Value)
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>;
 
-impl<T> Input for Stdin<T>
+impl<Key, Value> Input for FlagEq<Key, Value>
 where
-    T: AsRef<[u8]>,
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>,
 {
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        match config.stdin.as_mut() {
-            Some(arc) => {
-                Arc::make_mut(arc).extend_from_slice(self.0.as_ref());
-            }
-            None => {
-                config.stdin = Some(Arc::new(self.0.as_ref().to_vec()));
-            }
-        }
+        let Self(key, value) = self;
+        let mut argument = key.as_ref().to_os_string();
+        argument.push("=");
+        argument.push(value);
+        argument.configure(config);
     }
 }
 
-/// Adds an environment variable to the environment of the child process.
+/// Passes the given flag in front of every value,
+/// e.g. `-I foo -I bar`:
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// let StdoutUntrimmed(output) = run_output!("env", Env("FOO", "bar"));
-/// assert!(output.contains("FOO=bar\n"));
+/// let include_dirs = vec!["foo", "bar"];
+/// let StdoutTrimmed(output) = run_output!("echo", Repeat("-I", include_dirs));
+/// assert_eq!(output, "-I foo -I bar");
 /// ```
 ///
-/// Child processes inherit the environment of the parent process.
-/// [`Env`] only adds environment variables to that inherited environment.
-/// If the environment variable is also set in the parent process,
-/// it is overwritten by [`Env`].
+/// If there are no values, the flag is not passed at all.
 #[derive(Debug, Clone)]
-pub struct Env<Key, Value>(pub Key, pub Value)
+pub struct Repeat<Key, Values>(pub Key, pub Values)
 where
-    Key: AsRef<OsStr>,
-    Value: AsRef<OsStr>;
+    Key: Input + Clone,
+    Values: IntoIterator,
+    Values::Item: Input;
 
-impl<Key, Value> Input for Env<Key, Value>
+impl<Key, Values> Input for Repeat<Key, Values>
 where
-    Key: AsRef<OsStr>,
-    Value: AsRef<OsStr>,
+    Key: Input + Clone,
+    Values: IntoIterator,
+    Values::Item: Input,
 {
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        let Self(key, value) = self;
-        config
-            .added_environment_variables
-            .push((key.as_ref().to_os_string(), value.as_ref().to_os_string()));
+        let Self(key, values) = self;
+        for value in values {
+            Flag(key.clone(), value).configure(config);
+        }
+    }
+}
+
+/// A reusable command, that accumulates [`Input`]s.
+/// A base command can be defined once and specialized at call sites:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # let repo = temp_dir.path().to_path_buf();
+/// use cradle::prelude::*;
+///
+/// let git = Cmd::new("git")
+///     .arg("-C")
+///     .arg(&repo)
+///     .input(Env("GIT_PAGER", "cat"));
+/// git.clone().args(vec!["init", "--quiet"]).run();
+/// let StdoutTrimmed(output) = git.arg("status").arg("--short").run_output();
+/// assert_eq!(output, "");
+/// ```
+///
+/// [`Cmd`] implements [`Input`], so it can be combined with other inputs,
+/// and passed to [`run!`] and friends:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let mut echo = Cmd::new("echo");
+/// let verbose = true;
+/// if verbose {
+///     echo = echo.arg("--verbose");
+/// }
+/// let StdoutTrimmed(output) = run_output!(echo, "foo");
+/// assert_eq!(output, "--verbose foo");
+/// ```
+///
+/// In contrast to tuples, [`Cmd`] has a nameable type,
+/// so it can be stored in struct fields,
+/// and it can be extended conditionally.
+/// Cloning a [`Cmd`] is cheap, the inputs are shared between clones.
+#[derive(Clone, Default)]
+pub struct Cmd {
+    inputs: Vec<CmdInput>,
+}
+
+/// An [`Input`] stored in a [`Cmd`], that can be applied multiple times.
+#[derive(Clone)]
+struct CmdInput {
+    configure: Arc<dyn Fn(&mut Config) + Send + Sync>,
+    /// The `Debug` output of the input, rendered when it was added.
+    debug: Arc<str>,
+}
+
+impl Cmd {
+    /// Creates a [`Cmd`] that runs the given executable.
+    pub fn new(executable: impl AsRef<OsStr>) -> Cmd {
+        Cmd::default().arg(executable)
+    }
+
+    /// Adds an argument.
+    pub fn arg(self, argument: impl AsRef<OsStr>) -> Cmd {
+        self.input(argument.as_ref().to_os_string())
+    }
+
+    /// Adds multiple arguments.
+    pub fn args<I>(self, arguments: I) -> Cmd
+    where
+        I: IntoIterator,
+        I::Item: AsRef<OsStr>,
+    {
+        arguments
+            .into_iter()
+            .fold(self, |cmd, argument| cmd.arg(argument))
+    }
+
+    /// Adds an arbitrary [`Input`], e.g. [`CurrentDir`] or [`Env`].
+    /// Since a [`Cmd`] can be used multiple times, the input is cloned
+    /// every time the [`Cmd`] is used.
+    pub fn input<I>(mut self, input: I) -> Cmd
+    where
+        I: Input + Clone + fmt::Debug + Send + Sync + 'static,
+    {
+        let debug = format!("{:?}", input).into();
+        self.inputs.push(CmdInput {
+            configure: Arc::new(move |config| input.clone().configure(config)),
+            debug,
+        });
+        self
+    }
+}
+
+impl Input for Cmd {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for input in self.inputs {
+            (input.configure)(config);
+        }
+    }
+}
+
+/// Shows the `Debug` output of the inputs, with [`Secret`]s redacted.
+/// The inputs are not applied, so e.g. an [`EnvFile`] is not read.
+impl fmt::Debug for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_tuple("Cmd");
+        for input in &self.inputs {
+            debug.field(&format_args!("{}", input.debug));
+        }
+        debug.finish()
+    }
+}
+
+/// Marks the arguments and environment variable values that the given input adds
+/// as secret.
+/// They are passed to the child process unchanged,
+/// but are rendered as `***` in logged commands (see [`LogCommand`]),
+/// in error messages and in the `Debug` output of [`Config`]:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let token = "my-token";
+/// let result: Result<(), Error> = run_result!(
+///     %"false --header",
+///     Secret(format!("Authorization: Bearer {}", token))
+/// );
+/// assert_eq!(
+///     result.unwrap_err().to_string(),
+///     "false --header ***:\n  exited with exit code: 1"
+/// );
+/// ```
+///
+/// To redact the value of an environment variable, wrap [`Env`] in [`Secret`]:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $TOKEN", Secret(Env("TOKEN", "my-token")));
+/// assert_eq!(output, "my-token");
+/// ```
+///
+/// When [`Secret`] wraps [`Stdin`], the whole `stdin` is redacted
+/// in the `Debug` output of [`Config`].
+/// A `PATH` that was set as a secret stays secret when
+/// [`PathPrepend`] or [`PathAppend`] modify it.
+///
+/// Environment variable keys are not redacted.
+/// Neither are secrets that end up in the output of the child process,
+/// nor values that [`EnvFile`] derives from secret environment variables
+/// with `${VAR}` references, unless the [`EnvFile`] is wrapped in [`Secret`] as well.
+#[derive(Clone)]
+pub struct Secret<T: Input>(pub T);
+
+/// Doesn't show the wrapped input:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// assert_eq!(format!("{:?}", Secret("my-token")), "Secret(***)");
+/// ```
+impl<T: Input> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl<T> Input for Secret<T>
+where
+    T: Input,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let arguments_before = config.arguments.len();
+        let environment_variables_before = config.added_environment_variables.len();
+        let stdin_before = stdin_length(config);
+        self.0.configure(config);
+        if stdin_length(config) != stdin_before {
+            config.secret_stdin = true;
+        }
+        config
+            .secret_arguments
+            .extend(arguments_before..config.arguments.len());
+        config
+            .secret_environment_variables
+            .extend(environment_variables_before..config.added_environment_variables.len());
+    }
+}
+
+fn stdin_length(config: &Config) -> usize {
+    config.stdin.as_ref().map_or(0, |stdin| stdin.len())
+}
+
+/// Passing in [`LogCommand`] as an argument to `cradle` will cause it
+/// to log the commands (including all arguments) to `stderr`.
+/// (This is similar `bash`'s `-x` option.)
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(LogCommand, %"echo foo");
+/// // writes '+ echo foo' to stderr
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct LogCommand;
+
+impl Input for LogCommand {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.log_command = true;
+    }
+}
+
+/// By default, `cradle` makes sure that child processes don't outlive
+/// the call that started them.
+/// If the calling thread panics while a child process is still running --
+/// e.g. because relaying the child's output panicked --
+/// or if a [`Handle`] is dropped without waiting for
+/// the child, the child is sent a `SIGTERM` and, if it doesn't exit within
+/// a grace period of one second, killed with `SIGKILL`.
+/// (On non-unix platforms it is killed right away.)
+/// The signal and the grace period can be configured with [`GracefulStop`].
+///
+/// [`KillOnDrop`] allows to switch that off:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(%"echo foo", KillOnDrop(false));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct KillOnDrop(pub bool);
+
+impl Input for KillOnDrop {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.kill_on_drop = self.0;
+    }
+}
+
+/// Configures how child processes are stopped when they have to be killed,
+/// e.g. when a [`Handle`] is dropped or
+/// [stopped](crate::handle::Handle::stop):
+/// First `signal` is sent to the child.
+/// If the child is still running after `grace`, it's killed with `SIGKILL`.
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::time::Duration;
+///
+/// let handle = spawn!(
+///     %"sleep 1000",
+///     GracefulStop {
+///         signal: Signal::SIGINT,
+///         grace: Duration::from_secs(5),
+///     },
+/// )
+/// .unwrap();
+/// let Status(status) = handle.stop().unwrap();
+/// assert!(!status.success());
+/// ```
+///
+/// The default is to send `SIGTERM`, with a grace period of one second.
+///
+/// Only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GracefulStop {
+    pub signal: Signal,
+    pub grace: Duration,
+}
+
+#[cfg(unix)]
+impl Default for GracefulStop {
+    fn default() -> Self {
+        GracefulStop {
+            signal: Signal::SIGTERM,
+            grace: Duration::from_secs(1),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Input for GracefulStop {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.graceful_stop = self;
+    }
+}
+
+/// Starts the child process in a new process group
+/// (by calling `setpgid(0, 0)` before executing it).
+/// When the child gets killed -- see [`KillOnDrop`] --
+/// all processes in that group are signalled,
+/// including e.g. grandchildren that the child has spawned.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let StdoutTrimmed(output) =
+///     run_output!("sh", "-c", "ps -o pid= -o pgid= -p $$", NewProcessGroup);
+/// let ids = output.split_whitespace().collect::<Vec<_>>();
+/// // the child is the leader of its own process group
+/// assert_eq!(ids[0], ids[1]);
+/// # }
+/// ```
+///
+/// Only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct NewProcessGroup;
+
+#[cfg(unix)]
+impl Input for NewProcessGroup {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.process_group = ProcessGroup::New;
+    }
+}
+
+/// Starts the child process in a new session (by calling `setsid` before
+/// executing it). This also creates a new process group and detaches the
+/// child from the parent's controlling terminal.
+/// As with [`NewProcessGroup`], killing the child signals the whole group.
+///
+/// Only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct NewSession;
+
+#[cfg(unix)]
+impl Input for NewSession {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.process_group = ProcessGroup::NewSession;
+    }
+}
+
+/// Runs the child process attached to a pseudo-terminal,
+/// instead of connecting its standard streams to pipes.
+/// This makes the child believe it's running in an interactive terminal,
+/// so many tools will e.g. emit colors or progress bars.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let StdoutTrimmed(output) = run_output!("sh", "-c", "test -t 1 && echo terminal", Pty);
+/// assert_eq!(output, "terminal");
+/// # }
+/// ```
+///
+/// Things to note when using [`Pty`]:
+///
+/// - The child's `stdout` and `stderr` are both written to the pseudo-terminal,
+///   so they can't be told apart anymore.
+///   Both are relayed to the parent's `stdout`, or captured with
+///   e.g. [`StdoutUntrimmed`](crate::output::StdoutUntrimmed).
+///   [`Stderr`](crate::output::Stderr) will always capture an empty string.
+/// - Terminals translate line endings, so captured output will
+///   contain `\r\n` instead of `\n`.
+/// - Terminals echo their input, so bytes written with [`Stdin`] will
+///   show up in the output.
+/// - The child's `stdin` can't be closed without hanging up the terminal.
+///   Instead, after writing the bytes from [`Stdin`], the terminal's
+///   end-of-file character (usually `^D`) is sent.
+///   Children that switch the terminal to raw mode will read it as a normal byte.
+/// - The child is started in a new session, with the pseudo-terminal as its
+///   controlling terminal. So killing the child signals its whole process group,
+///   as with [`NewSession`].
+///
+/// The size of the pseudo-terminal is taken from the parent's terminal.
+/// While the child is running, changes to the size of the parent's terminal
+/// are forwarded to the pseudo-terminal, which sends `SIGWINCH` to the child.
+/// If the parent is not running in a terminal, 24 rows and 80 columns are used.
+/// The size can be fixed with [`PtySize`], which disables the forwarding.
+///
+/// Only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct Pty;
+
+#[cfg(unix)]
+impl Input for Pty {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.pty = true;
+    }
+}
+
+/// Sets the window size of the pseudo-terminal used with [`Pty`].
+/// Has no effect without [`Pty`].
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let StdoutTrimmed(output) = run_output!(
+///     %"stty size",
+///     Pty,
+///     PtySize {
+///         rows: 40,
+///         columns: 100
+///     },
+/// );
+/// assert_eq!(output, "40 100");
+/// # }
+/// ```
+///
+/// Only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PtySize {
+    pub rows: u16,
+    pub columns: u16,
+}
+
+#[cfg(unix)]
+impl Input for PtySize {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.pty_size = Some(self);
+    }
+}
+
+/// By default child processes inherit the current directory from their
+/// parent. You can override this with [`CurrentDir`]:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let StdoutTrimmed(output) = run_output!("pwd", CurrentDir("/tmp"));
+/// assert_eq!(output, "/tmp");
+/// # }
+/// ```
+///
+/// Paths that are relative to the parent's current directory are allowed.
+#[derive(Debug, Clone)]
+pub struct CurrentDir<T: AsRef<Path>>(pub T);
+
+impl<T> Input for CurrentDir<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.working_directory = Some(self.0.as_ref().to_owned());
+    }
+}
+
+/// Arguments of type [`PathBuf`] are passed to the child process
+/// as arguments.
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::path::PathBuf;
+///
+/// let current_dir: PathBuf = std::env::current_dir().unwrap();
+/// run!("ls", current_dir);
+/// ```
+impl Input for PathBuf {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        self.into_os_string().configure(config);
+    }
+}
+
+/// Arguments of type [`&Path`] are passed to the child process
+/// as arguments.
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+/// use std::path::Path;
+///
+/// let file: &Path = Path::new("./foo");
+/// run!("touch", file);
+/// ```
+///
+/// [`&Path`]: std::path::Path
+impl Input for &Path {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        self.as_os_str().to_os_string().configure(config);
+    }
+}
+
+/// Writes the given byte slice to the child's standard input.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let StdoutUntrimmed(output) = run_output!("sort", Stdin("foo\nbar\n"));
+/// assert_eq!(output, "bar\nfoo\n");
+/// # }
+/// ```
+///
+/// If `Stdin` is used multiple times, all given bytes slices will be written
+/// to the child's standard input in order.
+#[derive(Debug, Clone)]
+pub struct Stdin<T: AsRef<[u8]>>(pub T);
+
+impl<T> Input for Stdin<T>
+where
+    T: AsRef<[u8]>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        match config.stdin.as_mut() {
+            Some(arc) => {
+                Arc::make_mut(arc).extend_from_slice(self.0.as_ref());
+            }
+            None => {
+                config.stdin = Some(Arc::new(self.0.as_ref().to_vec()));
+            }
+        }
+    }
+}
+
+/// Writes the given strings to the child's standard input,
+/// each terminated by a NUL byte.
+/// This is the format that e.g. `xargs -0` expects,
+/// and allows to pass file names that contain whitespace
+/// or newlines:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let files = vec!["foo bar", "baz\nqux"];
+/// let StdoutUntrimmed(output) =
+///     run_output!(%"xargs -0 printf [%s]", StdinNulSeparated(files));
+/// assert_eq!(output, "[foo bar][baz\nqux]");
+/// # }
+/// ```
+///
+/// On unix, the bytes of the [`OsStr`]s are written as they are,
+/// so non-utf-8 file names are passed through unchanged.
+/// On other platforms, they are converted to utf-8 lossily.
+/// [`StdinNulSeparated`] can be combined with [`Stdin`];
+/// all bytes are written in order.
+#[derive(Debug, Clone)]
+pub struct StdinNulSeparated<T>(pub T)
+where
+    T: IntoIterator,
+    T::Item: AsRef<OsStr>;
+
+impl<T> Input for StdinNulSeparated<T>
+where
+    T: IntoIterator,
+    T::Item: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let mut bytes = Vec::new();
+        for item in self.0 {
+            bytes.extend_from_slice(&os_str_to_bytes(item.as_ref()));
+            bytes.push(0);
+        }
+        Stdin(bytes).configure(config);
+    }
+}
+
+#[cfg(unix)]
+fn os_str_to_bytes(os_str: &OsStr) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    std::borrow::Cow::Borrowed(os_str.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn os_str_to_bytes(os_str: &OsStr) -> std::borrow::Cow<'_, [u8]> {
+    std::borrow::Cow::Owned(os_str.to_string_lossy().into_owned().into_bytes())
+}
+
+/// Passes the parent's `stdin`, `stdout` and `stderr` directly to the
+/// child process.
+/// This is a shortcut for using [`StdinInherit`], [`StdoutInherit`]
+/// and [`StderrInherit`] together.
+///
+/// By default, `cradle` connects the child's standard streams to pipes
+/// and relays them in background threads.
+/// That means the child never sees the parent's terminal.
+/// With [`Inherit`], interactive programs like editors
+/// or password prompts work as expected:
+///
+/// ```no_run
+/// use cradle::prelude::*;
+///
+/// run!(%"git commit", Inherit);
+/// ```
+///
+/// Streams that are captured, e.g. with [`StdoutTrimmed`](crate::output::StdoutTrimmed),
+/// or written to with [`Stdin`], are not inherited:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(%"echo foo", Inherit);
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// Inherited streams bypass the relaying threads entirely, so e.g. tests
+/// can't observe output written to them.
+#[derive(Debug, Clone, Copy)]
+pub struct Inherit;
+
+impl Input for Inherit {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        (StdinInherit, StdoutInherit, StderrInherit).configure(config);
+    }
+}
+
+/// Passes the parent's `stdin` directly to the child process.
+///
+/// Without it, the child's `stdin` is closed right away,
+/// or after writing the bytes given with [`Stdin`].
+/// If [`Stdin`] is used, [`StdinInherit`] has no effect.
+///
+/// ```no_run
+/// use cradle::prelude::*;
+///
+/// // asks the user for confirmation
+/// run!(%"rm -i foo", StdinInherit);
+/// ```
+///
+/// See also [`Inherit`].
+#[derive(Debug, Clone, Copy)]
+pub struct StdinInherit;
+
+impl Input for StdinInherit {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.inherit_stdin = true;
+    }
+}
+
+/// Passes the parent's `stdout` directly to the child process,
+/// instead of relaying it.
+/// If `stdout` is captured, e.g. with [`StdoutTrimmed`](crate::output::StdoutTrimmed),
+/// [`StdoutInherit`] has no effect.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(%"echo foo", StdoutInherit);
+/// ```
+///
+/// See also [`Inherit`].
+#[derive(Debug, Clone, Copy)]
+pub struct StdoutInherit;
+
+impl Input for StdoutInherit {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.inherit_stdout = true;
+    }
+}
+
+/// Passes the parent's `stderr` directly to the child process,
+/// instead of relaying it.
+/// If `stderr` is captured with [`Stderr`](crate::output::Stderr),
+/// [`StderrInherit`] has no effect.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Status(status) = run_output!("ls", "does-not-exist", StderrInherit);
+/// assert!(!status.success());
+/// ```
+///
+/// See also [`Inherit`].
+#[derive(Debug, Clone, Copy)]
+pub struct StderrInherit;
+
+impl Input for StderrInherit {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.inherit_stderr = true;
+    }
+}
+
+/// Adds an environment variable to the environment of the child process.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutUntrimmed(output) = run_output!("env", Env("FOO", "bar"));
+/// assert!(output.contains("FOO=bar\n"));
+/// ```
+///
+/// Child processes inherit the environment of the parent process.
+/// [`Env`] only adds environment variables to that inherited environment.
+/// If the environment variable is also set in the parent process,
+/// it is overwritten by [`Env`].
+#[derive(Debug, Clone)]
+pub struct Env<Key, Value>(pub Key, pub Value)
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>;
+
+impl<Key, Value> Input for Env<Key, Value>
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let Self(key, value) = self;
+        config
+            .added_environment_variables
+            .push((key.as_ref().to_os_string(), value.as_ref().to_os_string()));
+    }
+}
+
+/// Reads environment variables from a `.env` file and adds them to the
+/// environment of the child process, in the order they appear in the file:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// std::fs::write(
+///     ".env",
+///     "# database settings\nexport DB_HOST=localhost\nDB_URL=\"postgres://${DB_HOST}/app\"\n",
+/// )
+/// .unwrap();
+/// let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $DB_URL", EnvFile(".env"));
+/// assert_eq!(output, "postgres://localhost/app");
+/// ```
+///
+/// The supported syntax is:
+///
+/// - `KEY=value` assignments, one per line, optionally prefixed with `export`,
+/// - empty lines and comments starting with `#`,
+///   also after values, if preceded by whitespace,
+/// - single-quoted values, which are taken literally,
+/// - double-quoted values, which support the escapes `\n`, `\t`, `\r`,
+///   `\"`, `\\` and `\$`,
+/// - `${VAR}` references in unquoted and double-quoted values,
+///   which expand to variables defined earlier in the file, or added before with [`Env`],
+///   or inherited from the parent process.
+///   Undefined variables expand to the empty string.
+///   `$$` is a literal `$`.
+///   Values that reference [`Secret`] variables are not redacted,
+///   unless the [`EnvFile`] is wrapped in [`Secret`].
+///
+/// Relative paths are resolved against the working directory of the child,
+/// if it was set with a [`CurrentDir`] that comes before the [`EnvFile`].
+/// The file is read when the [`EnvFile`] is configured,
+/// not when the child process is spawned.
+///
+/// Files that can't be read result in an [`Error::CommandIoError`],
+/// syntax errors in an [`Error::ParseEnvFile`], when the child process is spawned.
+#[derive(Debug, Clone)]
+pub struct EnvFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for EnvFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let path = match &config.working_directory {
+            Some(working_directory) => working_directory.join(self.0.as_ref()),
+            None => self.0.as_ref().to_path_buf(),
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(error) => {
+                return config.add_configuration_error(ConfigurationError::ReadEnvFile {
+                    path,
+                    source: Arc::new(error),
+                })
+            }
+        };
+        let lookup = |name: &str| {
+            config
+                .added_environment_variables
+                .iter()
+                .rev()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| {
+                    if config.clear_environment
+                        || config
+                            .removed_environment_variables
+                            .contains(OsStr::new(name))
+                    {
+                        None
+                    } else {
+                        std::env::var_os(name)
+                    }
+                })
+        };
+        match env_file::parse(&content, lookup) {
+            Ok(variables) => config.added_environment_variables.extend(variables),
+            Err(error) => config.add_configuration_error(ConfigurationError::ParseEnvFile {
+                path,
+                line: error.line,
+                message: error.message,
+            }),
+        }
+    }
+}
+
+/// Adds a directory to the front of the child's `PATH`,
+/// so that executables in it take precedence:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// # std::fs::create_dir_all("node_modules/.bin").unwrap();
+/// let StdoutTrimmed(path) = run_output!(%"sh -c", "echo $PATH", PathPrepend("node_modules/.bin"));
+/// assert!(path.starts_with("node_modules/.bin:"));
+/// # }
+/// ```
+///
+/// The directory is added to the `PATH` of the parent process,
+/// or to a `PATH` that was set before with [`Env`].
+/// Multiple [`PathPrepend`]s and [`PathAppend`]s can be combined,
+/// they are applied in order.
+/// The resulting `PATH` is also used to find the executable.
+///
+/// Directories that contain the path separator (`:` on unix, `;` on windows)
+/// result in an [`Error::InvalidPathDirectory`].
+#[derive(Debug, Clone)]
+pub struct PathPrepend<T: AsRef<Path>>(pub T);
+
+impl<T> Input for PathPrepend<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        add_to_path(config, self.0.as_ref(), |directories, directory| {
+            directories.insert(0, directory)
+        });
+    }
+}
+
+/// Adds a directory to the end of the child's `PATH`,
+/// so that it's only searched for executables
+/// that are not found in any other directory.
+/// Otherwise the same as [`PathPrepend`].
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutTrimmed(path) = run_output!(%"sh -c", "echo $PATH", PathAppend("/opt/tools/bin"));
+/// assert!(path.ends_with(":/opt/tools/bin"));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PathAppend<T: AsRef<Path>>(pub T);
+
+impl<T> Input for PathAppend<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        add_to_path(config, self.0.as_ref(), |directories, directory| {
+            directories.push(directory)
+        });
+    }
+}
+
+fn add_to_path(
+    config: &mut Config,
+    directory: &Path,
+    add: impl FnOnce(&mut Vec<PathBuf>, PathBuf),
+) {
+    let mut directories = match config.path_variable() {
+        Some(path) if !path.is_empty() => std::env::split_paths(&path).collect(),
+        _ => Vec::new(),
+    };
+    add(&mut directories, directory.to_path_buf());
+    match std::env::join_paths(directories) {
+        Ok(path) if config.path_variable_is_secret() => Secret(Env("PATH", path)).configure(config),
+        Ok(path) => Env("PATH", path).configure(config),
+        Err(_) => config.add_configuration_error(ConfigurationError::InvalidPathDirectory(
+            directory.to_path_buf(),
+        )),
+    }
+}
+
+/// Uses the program, arguments, environment variables and
+/// working directory of a [`std::process::Command`]:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::process::Command;
+///
+/// let mut command = Command::new("echo");
+/// command.arg("foo");
+/// let StdoutTrimmed(output) = run_output!(command, "bar");
+/// assert_eq!(output, "foo bar");
+/// ```
+///
+/// Environment variables removed with [`Command::env_remove`] are
+/// removed from the child's environment as well.
+/// After [`Command::env_clear`] the child doesn't inherit any
+/// environment variables, neither from the parent process
+/// nor from inputs that come before the [`Command`].
+/// The standard streams of the [`Command`] are not taken into account.
+///
+/// Only works on rust version `1.57` and up.
+/// [`Command`] has no getter for [`Command::env_clear`],
+/// so it's detected through the [`Debug`](std::fmt::Debug) output of the [`Command`].
+/// On older rust versions that don't include it there,
+/// [`Command::env_clear`] is ignored.
+///
+/// [`Command`]: std::process::Command
+/// [`Command::env_remove`]: std::process::Command::env_remove
+/// [`Command::env_clear`]: std::process::Command::env_clear
+#[rustversion::since(1.57)]
+impl Input for std::process::Command {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        self.get_program().configure(config);
+        for argument in self.get_args() {
+            argument.configure(config);
+        }
+        if format!("{:#?}", self).contains("clear: true") {
+            config.clear_environment_variables();
+        }
+        for (key, value) in self.get_envs() {
+            match value {
+                Some(value) => Env(key, value).configure(config),
+                None => config.remove_environment_variable(key),
+            }
+        }
+        if let Some(directory) = self.get_current_dir() {
+            CurrentDir(directory).configure(config);
+        }
     }
 }
@@ -1,4 +1,10 @@
 #![deny(missing_debug_implementations)]
+// `Error::Internal` carries the whole `Config` for bug reports.
+// Boxing it would be a breaking change.
+#![allow(unknown_lints, clippy::result_large_err, clippy::large_enum_variant)]
+// Examples like `run_result!(...)?;` rely on `()` as the never type fallback,
+// which newer compilers reject by default.
+#![doc(test(attr(allow(unknown_lints, dependency_on_unit_never_type_fallback))))]
 
 //! `cradle` provides the [`run!`] macro, that makes
 //! it easy to run child processes from rust programs.
@@ -113,6 +119,21 @@
 //! assert_eq!(output, "foo");
 //! ```
 //!
+//! If you enable the `macros` feature, `cradle` also provides the `cmd!` macro.
+//! It splits command lines at compile time, and allows to interpolate
+//! rust expressions, that will never be split:
+//!
+//! ```
+//! use cradle::prelude::*;
+//!
+//! # #[cfg(feature = "macros")]
+//! # {
+//! let message = "foo bar";
+//! let StdoutUntrimmed(output) = run_output!(cmd!("printf %s\\n {message}"));
+//! assert_eq!(output, "foo bar\n");
+//! # }
+//! ```
+//!
 //! # Error Handling
 //!
 //! **tl;dr:** [`run!`] and [`run_output!`] will panic on errors,
@@ -220,6 +241,37 @@
 //! Split("echo foo").run();
 //! ```
 //!
+//! # Spawning Child Processes
+//!
+//! [`run!`] and friends wait for the child process to finish.
+//! If you want to interact with a child process while it's running,
+//! use [`spawn!`] (or [`Input::spawn`]) to get a [`Handle`] to it:
+//!
+//! ```
+//! use cradle::prelude::*;
+//!
+//! let handle = spawn!(%"echo foo").unwrap();
+//! let StdoutTrimmed(output) = handle.wait().unwrap();
+//! assert_eq!(output, "foo");
+//! ```
+//!
+//! For scripted interaction with e.g. REPLs, enable the `session` feature
+//! and use `session!` (or `Input::session`) to get a `Session`.
+//! It allows to write to the child's `stdin` step by step,
+//! and to wait for patterns in its `stdout`:
+//!
+//! ```
+//! use cradle::prelude::*;
+//! use std::time::Duration;
+//!
+//! # #[cfg(feature = "session")]
+//! # {
+//! let mut session = session!("cat").unwrap();
+//! session.send_line("foo").unwrap();
+//! session.expect("foo", Duration::from_secs(5)).unwrap();
+//! # }
+//! ```
+//!
 //! # Prior Art
 //!
 //! `cradle` is heavily inspired by [shake](https://shakebuild.com/),
@@ -227,15 +279,24 @@
 //! [`cmd`](https://hackage.haskell.org/package/shake-0.19.4/docs/Development-Shake.html#v:cmd)
 //! function.
 
+mod child_guard;
 pub mod child_output;
 mod collected_output;
 pub mod config;
 mod context;
+pub mod defaults;
+mod env_file;
 pub mod error;
+pub mod handle;
 pub mod input;
 mod macros;
 pub mod output;
 pub mod prelude;
+#[cfg(unix)]
+mod pty;
+#[cfg(feature = "session")]
+pub mod session;
+pub mod which;
 
 include!("common_re_exports.rs.snippet");
 
@@ -468,6 +529,7 @@ mod tests {
                     .contains("exited with exit code: 42"));
             }
 
+            #[cfg(not(unix))]
             #[test]
             fn missing_executable_file_error_message() {
                 let result: Result<(), Error> = run_result!("does-not-exist");
@@ -477,6 +539,18 @@ mod tests {
                 );
             }
 
+            #[cfg(unix)]
+            #[test]
+            fn missing_executable_file_error_message_lists_searched_directories() {
+                let result: Result<(), Error> =
+                    run_result!("does-not-exist", Env("PATH", "/foo:/bar"));
+                assert_eq!(
+                    result.unwrap_err().to_string(),
+                    "File not found error when executing 'does-not-exist'\n  \
+                     not found in PATH, searched: '/foo', '/bar'"
+                );
+            }
+
             #[test]
             fn missing_executable_file_error_can_be_matched_against() {
                 let result: Result<(), Error> = run_result!("does-not-exist");
@@ -526,6 +600,7 @@ mod tests {
             use pretty_assertions::assert_eq;
             use unindent::Unindent;
 
+            #[cfg(not(unix))]
             #[test]
             fn missing_executable_file_with_whitespace_includes_note() {
                 let result: Result<(), Error> = run_result!("does not exist");
@@ -541,6 +616,25 @@ mod tests {
                 assert_eq!(result.unwrap_err().to_string(), expected);
             }
 
+            #[cfg(unix)]
+            #[test]
+            fn missing_executable_file_with_whitespace_includes_note_on_unix() {
+                let result: Result<(), Error> =
+                    run_result!("does not exist", Env("PATH", "/does-not-exist"));
+                let expected = "
+                    File not found error when executing 'does not exist'
+                      not found in PATH, searched: '/does-not-exist'
+                    note: Given executable name 'does not exist' contains whitespace.
+                      Did you mean to run 'does', with 'not' and 'exist' as arguments?
+                      Consider using Split: https://docs.rs/cradle/latest/cradle/input/struct.Split.html
+                "
+                .unindent()
+                .trim()
+                .to_string();
+                assert_eq!(result.unwrap_err().to_string(), expected);
+            }
+
+            #[cfg(not(unix))]
             #[test]
             fn single_argument() {
                 let result: Result<(), Error> = run_result!("foo bar");
@@ -555,6 +649,24 @@ mod tests {
                 .to_string();
                 assert_eq!(result.unwrap_err().to_string(), expected);
             }
+
+            #[cfg(unix)]
+            #[test]
+            fn single_argument_on_unix() {
+                let result: Result<(), Error> =
+                    run_result!("foo bar", Env("PATH", "/does-not-exist"));
+                let expected = "
+                    File not found error when executing 'foo bar'
+                      not found in PATH, searched: '/does-not-exist'
+                    note: Given executable name 'foo bar' contains whitespace.
+                      Did you mean to run 'foo', with 'bar' as the argument?
+                      Consider using Split: https://docs.rs/cradle/latest/cradle/input/struct.Split.html
+                "
+                .unindent()
+                .trim()
+                .to_string();
+                assert_eq!(result.unwrap_err().to_string(), expected);
+            }
         }
     }
 
@@ -688,768 +800,3138 @@ mod tests {
         }
     }
 
-    mod strings {
+    mod iterators {
         use super::*;
+        use pretty_assertions::assert_eq;
+        use std::collections::{BTreeSet, LinkedList, VecDeque};
 
         #[test]
-        fn works_for_string() {
-            let command: String = "true".to_string();
-            run!(command);
+        fn args_uses_all_items() {
+            let StdoutTrimmed(output) = run_output!("echo", Args(vec!["foo", "bar"]));
+            assert_eq!(output, "foo bar");
         }
 
         #[test]
-        fn multiple_strings() {
-            let command: String = "echo".to_string();
-            let argument: String = "foo".to_string();
-            let StdoutTrimmed(output) = run_output!(command, argument);
-            assert_eq!(output, "foo");
+        fn args_works_for_iterator_adapters() {
+            let numbers = [1, 2, 3, 4];
+            let StdoutTrimmed(output) = run_output!(
+                "echo",
+                Args(numbers.iter().filter(|n| *n % 2 == 0).map(|n| n * 10))
+            );
+            assert_eq!(output, "20 40");
         }
 
         #[test]
-        fn mix_ref_str_and_string() {
-            let argument: String = "foo".to_string();
-            let StdoutTrimmed(output) = run_output!("echo", argument);
-            assert_eq!(output, "foo");
+        fn args_works_for_chained_iterators() {
+            let StdoutTrimmed(output) =
+                run_output!(Args(["echo", "foo"].iter().chain(["bar"].iter())));
+            assert_eq!(output, "foo bar");
         }
 
         #[test]
-        fn does_not_split_strings_in_vectors() {
-            in_temporary_directory(|| {
-                let argument: Vec<String> = vec!["filename with spaces".to_string()];
-                run!("touch", argument);
-                assert!(PathBuf::from("filename with spaces").exists());
-            });
+        fn args_works_for_non_argument_inputs() {
+            let StdoutTrimmed(output) = run_output!("cat", Args(vec![Stdin("foo"), Stdin("bar")]));
+            assert_eq!(output, "foobar");
         }
-    }
 
-    mod os_strings {
-        use super::*;
+        #[test]
+        fn empty_args_contribute_nothing() {
+            let StdoutTrimmed(output) = run_output!("echo", Args(Vec::<String>::new()), "foo");
+            assert_eq!(output, "foo");
+        }
 
         #[test]
-        fn works_for_os_string() {
-            run!(OsString::from("true"));
+        fn vec_deques() {
+            let StdoutTrimmed(output) = run_output!("echo", VecDeque::from(vec!["foo", "bar"]));
+            assert_eq!(output, "foo bar");
         }
 
         #[test]
-        fn works_for_os_str() {
-            run!(OsStr::new("true"));
+        fn linked_lists() {
+            let list: LinkedList<&str> = vec!["foo", "bar"].into_iter().collect();
+            let StdoutTrimmed(output) = run_output!("echo", list);
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn btree_sets() {
+            let set: BTreeSet<&str> = vec!["foo", "bar"].into_iter().collect();
+            let StdoutTrimmed(output) = run_output!("echo", set);
+            assert_eq!(output, "bar foo");
         }
     }
 
-    mod stdout {
+    mod cmd_builder {
         use super::*;
-        use std::{thread, time::Duration};
+        use pretty_assertions::assert_eq;
 
         #[test]
-        fn relays_stdout_by_default() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
-            assert_eq!(context.stdout(), "foo\n");
+        fn runs_the_executable_with_arguments() {
+            let StdoutTrimmed(output) = Cmd::new("echo").arg("foo").arg("bar").run_output();
+            assert_eq!(output, "foo bar");
         }
 
         #[test]
-        fn relays_stdout_for_non_zero_exit_codes() {
-            let context = Context::test();
-            let _ = run_result_with_context_unit(
-                context.clone(),
-                (test_helper(), "output foo and exit with 42"),
+        fn adds_multiple_arguments() {
+            let StdoutTrimmed(output) = Cmd::new("echo").args(vec!["foo", "bar"]).run_output();
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn adds_inputs() {
+            let StdoutTrimmed(output) = Cmd::new(test_helper())
+                .args(vec!["echo", "FOO"])
+                .input(Env("FOO", "bar"))
+                .run_output();
+            assert_eq!(output, "bar");
+        }
+
+        #[test]
+        fn can_be_extended_after_cloning() {
+            let echo = Cmd::new("echo").arg("foo");
+            let StdoutTrimmed(first) = echo.clone().arg("bar").run_output();
+            let StdoutTrimmed(second) = echo.clone().arg("baz").run_output();
+            let StdoutTrimmed(base) = echo.run_output();
+            assert_eq!(
+                (first, second, base),
+                (
+                    "foo bar".to_string(),
+                    "foo baz".to_string(),
+                    "foo".to_string()
+                )
             );
-            assert_eq!(context.stdout(), "foo\n");
         }
 
         #[test]
-        fn streams_stdout() {
-            in_temporary_directory(|| {
-                let context = Context::test();
-                let context_clone = context.clone();
-                let thread = thread::spawn(|| {
-                    run_result_with_context_unit(
-                        context_clone,
-                        (test_helper(), "stream chunk then wait for file"),
-                    )
-                    .unwrap();
-                });
-                while (context.stdout()) != "foo\n" {
-                    thread::sleep(Duration::from_secs_f32(0.05));
-                }
-                run!(%"touch file");
-                thread.join().unwrap();
-            });
+        fn can_be_combined_with_other_inputs() {
+            let StdoutTrimmed(output) = run_output!(Cmd::new("echo").arg("foo"), %"bar baz");
+            assert_eq!(output, "foo bar baz");
         }
 
         #[test]
-        fn does_not_relay_stdout_when_collecting_into_string() {
-            let context = Context::test();
-            let StdoutTrimmed(_) =
-                run_result_with_context(context.clone(), Split("echo foo")).unwrap();
-            assert_eq!(context.stdout(), "");
+        fn can_be_used_multiple_times() {
+            let echo = Cmd::new("echo").input(Split("foo bar"));
+            let StdoutTrimmed(first) = run_output!(&echo);
+            let StdoutTrimmed(second) = run_output!(&echo);
+            assert_eq!(first, "foo bar");
+            assert_eq!(second, "foo bar");
         }
 
         #[test]
-        fn does_not_relay_stdout_when_collecting_into_result_of_string() {
-            let context = Context::test();
-            let _: Result<StdoutTrimmed, Error> =
-                run_result_with_context(context.clone(), Split("echo foo"));
-            assert_eq!(context.stdout(), "");
+        fn default_is_empty() {
+            let StdoutTrimmed(output) = run_output!(Cmd::default(), %"echo foo");
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn can_be_stored_in_struct_fields() {
+            struct Tool {
+                command: Cmd,
+            }
+            let tool = Tool {
+                command: Cmd::new("echo").arg("--tool"),
+            };
+            let StdoutTrimmed(output) = tool.command.clone().arg("foo").run_output();
+            assert_eq!(output, "--tool foo");
+        }
+
+        #[test]
+        fn can_be_sent_to_other_threads() {
+            let echo = Cmd::new("echo").arg("foo");
+            let StdoutTrimmed(output) = std::thread::spawn(move || echo.run_output())
+                .join()
+                .unwrap();
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn debug_shows_the_inputs() {
+            let cmd = Cmd::new("curl")
+                .arg("foo bar")
+                .input(Secret("token"))
+                .input(Env("FOO", "bar"));
+            assert_eq!(
+                format!("{:?}", cmd),
+                r#"Cmd("curl", "foo bar", Secret(***), Env("FOO", "bar"))"#
+            );
+        }
+
+        #[test]
+        fn debug_does_not_apply_the_inputs() {
+            let cmd = Cmd::new("echo").input(EnvFile("does-not-exist.env"));
+            assert_eq!(
+                format!("{:?}", cmd),
+                r#"Cmd("echo", EnvFile("does-not-exist.env"))"#
+            );
         }
     }
 
-    mod stderr {
+    mod std_command {
         use super::*;
         use pretty_assertions::assert_eq;
-        use std::{thread, time::Duration};
+        use std::{path::Path, process::Command};
 
         #[test]
-        fn relays_stderr_by_default() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (test_helper(), "write to stderr"))
+        fn into_command_sets_arguments() {
+            let output = ("echo", "foo", "bar")
+                .into_command()
+                .unwrap()
+                .output()
                 .unwrap();
-            assert_eq!(context.stderr(), "foo\n");
+            assert_eq!(output.stdout, b"foo bar\n");
         }
 
         #[test]
-        fn relays_stderr_for_non_zero_exit_codes() {
-            let context = Context::test();
-            let _: Result<(), Error> = run_result_with_context(
-                context.clone(),
-                (test_helper(), "write to stderr and exit with 42"),
-            );
-            assert_eq!(context.stderr(), "foo\n");
+        fn into_command_sets_environment_variables() {
+            let output = (test_helper(), "echo", "FOO", Env("FOO", "bar"))
+                .into_command()
+                .unwrap()
+                .output()
+                .unwrap();
+            assert_eq!(output.stdout, b"bar\n");
         }
 
         #[test]
-        fn streams_stderr() {
+        fn into_command_sets_the_working_directory() {
             in_temporary_directory(|| {
-                let context = Context::test();
-                let context_clone = context.clone();
-                let thread = thread::spawn(|| {
-                    run_result_with_context_unit(
-                        context_clone,
-                        (test_helper(), "stream chunk to stderr then wait for file"),
-                    )
+                let directory = std::env::current_dir().unwrap().join("dir");
+                fs::create_dir(&directory).unwrap();
+                let output = ("pwd", CurrentDir(&directory))
+                    .into_command()
+                    .unwrap()
+                    .output()
                     .unwrap();
-                });
-                loop {
-                    let expected = "foo\n";
-                    let stderr = context.stderr();
-                    if stderr == expected {
-                        break;
-                    }
-                    assert!(
-                        stderr.len() <= expected.len(),
-                        "expected: {}, got: {}",
-                        expected,
-                        stderr
-                    );
-                    thread::sleep(Duration::from_secs_f32(0.05));
-                }
-                run!(%"touch file");
-                thread.join().unwrap();
+                let output = String::from_utf8(output.stdout).unwrap();
+                assert_eq!(
+                    fs::canonicalize(output.trim()).unwrap(),
+                    fs::canonicalize(directory).unwrap()
+                );
             });
         }
 
         #[test]
-        fn capture_stderr() {
-            let Stderr(stderr) = run_output!(test_helper(), "write to stderr");
-            assert_eq!(stderr, "foo\n");
+        fn into_command_without_arguments_fails() {
+            match Vec::<String>::new().into_command() {
+                Err(Error::NoExecutableGiven) => {}
+                _ => panic!("should match Error::NoExecutableGiven"),
+            }
         }
 
         #[test]
-        fn assumes_stderr_is_utf_8() {
-            let result: Result<Stderr, Error> = run_result!(test_helper(), "invalid utf-8 stderr");
-            assert_eq!(
-                result.unwrap_err().to_string(),
-                format!(
-                    "{} 'invalid utf-8 stderr':\n  invalid utf-8 written to stderr",
-                    test_helper().display(),
-                )
-            );
+        fn into_command_reports_configuration_errors() {
+            match ("echo", EnvFile("does-not-exist.env")).into_command() {
+                Err(Error::CommandIoError { .. }) => {}
+                _ => panic!("should match Error::CommandIoError"),
+            }
         }
 
         #[test]
-        #[cfg(not(windows))]
-        fn does_allow_invalid_utf_8_to_stderr_when_not_captured() {
-            run!(test_helper(), "invalid utf-8 stderr");
+        fn into_command_applies_defaults() {
+            let output = with_defaults(Env("FOO", "bar"), || {
+                (test_helper(), "echo", "FOO").into_command().unwrap()
+            })
+            .output()
+            .unwrap();
+            assert_eq!(output.stdout, b"bar\n");
         }
 
+        #[rustversion::since(1.57)]
         #[test]
-        fn does_not_relay_stderr_when_catpuring() {
-            let context = Context::test();
-            let Stderr(_) =
-                run_result_with_context(context.clone(), (test_helper(), "write to stderr"))
-                    .unwrap();
-            assert_eq!(context.stderr(), "");
+        fn command_is_an_input() {
+            let mut command = Command::new("echo");
+            command.arg("foo");
+            let StdoutTrimmed(output) = run_output!(command, "bar");
+            assert_eq!(output, "foo bar");
         }
-    }
-
-    mod log_commands {
-        use super::*;
 
+        #[rustversion::since(1.57)]
         #[test]
-        fn logs_simple_commands() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, "true")).unwrap();
-            assert_eq!(context.stderr(), "+ true\n");
+        fn command_environment_variables_are_used() {
+            let mut command = Command::new(test_helper());
+            command.args(["echo", "FOO"]).env("FOO", "bar");
+            let StdoutTrimmed(output) = run_output!(command);
+            assert_eq!(output, "bar");
         }
 
+        #[rustversion::since(1.57)]
         #[test]
-        fn logs_commands_with_arguments() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, Split("echo foo"))).unwrap();
-            assert_eq!(context.stderr(), "+ echo foo\n");
+        fn command_environment_variable_removals_are_used() {
+            let mut command = Command::new("sh");
+            command.args(["-c", "echo \"[$FOO]\""]).env_remove("FOO");
+            let StdoutTrimmed(output) = run_output!(Env("FOO", "bar"), command);
+            assert_eq!(output, "[]");
         }
 
+        #[rustversion::since(1.57)]
         #[test]
-        fn quotes_arguments_with_spaces() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "foo bar")).unwrap();
-            assert_eq!(context.stderr(), "+ echo 'foo bar'\n");
+        fn later_inputs_can_add_removed_environment_variables() {
+            let mut command = Command::new("sh");
+            command.args(["-c", "echo \"[$FOO]\""]).env_remove("FOO");
+            let StdoutTrimmed(output) = run_output!(command, Env("FOO", "bar"));
+            assert_eq!(output, "[bar]");
         }
 
+        #[cfg(unix)]
+        #[rustversion::since(1.57)]
         #[test]
-        fn quotes_empty_arguments() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "")).unwrap();
-            assert_eq!(context.stderr(), "+ echo ''\n");
+        fn command_environment_clears_are_used() {
+            let mut command = Command::new("/usr/bin/env");
+            command.env_clear().env("BAR", "baz");
+            let StdoutTrimmed(output) = run_output!(Env("FOO", "bar"), command);
+            assert_eq!(output, "BAR=baz");
+        }
+
+        #[cfg(unix)]
+        #[rustversion::since(1.57)]
+        #[test]
+        fn later_inputs_can_add_environment_variables_after_clears() {
+            let mut command = Command::new("/usr/bin/env");
+            command.env_clear();
+            let StdoutTrimmed(output) = run_output!(command, Env("FOO", "bar"));
+            assert_eq!(output, "FOO=bar");
+        }
+
+        #[cfg(unix)]
+        #[rustversion::since(1.57)]
+        #[test]
+        fn environment_clears_round_trip_through_config() {
+            let mut command = Command::new("/usr/bin/env");
+            command.env_clear();
+            let output = command.into_command().unwrap().output().unwrap();
+            assert_eq!(output.stdout, b"");
+        }
+
+        #[rustversion::since(1.57)]
+        #[test]
+        fn command_working_directory_is_used() {
+            in_temporary_directory(|| {
+                let directory = std::env::current_dir().unwrap().join("dir");
+                fs::create_dir(&directory).unwrap();
+                let mut command = Command::new("pwd");
+                command.current_dir(&directory);
+                let StdoutTrimmed(output) = run_output!(command);
+                assert_eq!(
+                    fs::canonicalize(output).unwrap(),
+                    fs::canonicalize(directory).unwrap()
+                );
+            });
+        }
+
+        #[rustversion::since(1.57)]
+        #[test]
+        fn round_trips_through_config() {
+            let mut command = Command::new("echo");
+            command.arg("foo").env("FOO", "bar").current_dir("/");
+            let command = command.into_command().unwrap();
+            assert_eq!(
+                (
+                    command.get_program(),
+                    command.get_args().collect::<Vec<_>>(),
+                    command.get_envs().collect::<Vec<_>>(),
+                    command.get_current_dir(),
+                ),
+                (
+                    OsStr::new("echo"),
+                    vec![OsStr::new("foo")],
+                    vec![(OsStr::new("FOO"), Some(OsStr::new("bar")))],
+                    Some(Path::new("/")),
+                )
+            );
+        }
+    }
+
+    mod flags {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn flag_passes_two_arguments() {
+            let StdoutUntrimmed(output) = run_output!("printf", "[%s]", Flag("--out", "foo bar"));
+            assert_eq!(output, "[--out][foo bar]");
+        }
+
+        #[test]
+        fn flag_values_can_be_any_input() {
+            let StdoutTrimmed(output) =
+                run_output!("echo", Flag("--count", 42), Flag("-x", None::<&str>));
+            assert_eq!(output, "--count 42 -x");
+        }
+
+        #[test]
+        fn flag_eq_passes_one_argument() {
+            let StdoutUntrimmed(output) =
+                run_output!("printf", "[%s]", FlagEq("--out", PathBuf::from("foo bar")));
+            assert_eq!(output, "[--out=foo bar]");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn flag_eq_works_for_non_utf8_values() {
+            use std::os::unix::ffi::OsStrExt;
+            let value = OsStr::from_bytes(&[b'f', 0xff]);
+            let mut config = crate::config::Config::default();
+            FlagEq("--out", value).configure(&mut config);
+            let mut expected = b"--out=".to_vec();
+            expected.extend_from_slice(value.as_bytes());
+            assert_eq!(
+                config.arguments,
+                vec![OsStr::from_bytes(&expected).to_os_string()]
+            );
+        }
+
+        #[test]
+        fn repeat_interleaves_the_flag() {
+            let StdoutTrimmed(output) = run_output!("echo", Repeat("-I", vec!["foo", "bar"]));
+            assert_eq!(output, "-I foo -I bar");
+        }
+
+        #[test]
+        fn repeat_without_values_contributes_nothing() {
+            let StdoutTrimmed(output) =
+                run_output!("echo", Repeat("-I", Vec::<String>::new()), "foo");
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn repeat_works_with_iterators() {
+            let dirs = [PathBuf::from("foo"), PathBuf::from("bar")];
+            let StdoutTrimmed(output) =
+                run_output!("echo", Repeat("-L", dirs.iter().map(|dir| dir.join("lib"))));
+            assert_eq!(output, "-L foo/lib -L bar/lib");
+        }
+    }
+
+    mod strings {
+        use super::*;
+
+        #[test]
+        fn works_for_string() {
+            let command: String = "true".to_string();
+            run!(command);
+        }
+
+        #[test]
+        fn multiple_strings() {
+            let command: String = "echo".to_string();
+            let argument: String = "foo".to_string();
+            let StdoutTrimmed(output) = run_output!(command, argument);
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn mix_ref_str_and_string() {
+            let argument: String = "foo".to_string();
+            let StdoutTrimmed(output) = run_output!("echo", argument);
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn does_not_split_strings_in_vectors() {
+            in_temporary_directory(|| {
+                let argument: Vec<String> = vec!["filename with spaces".to_string()];
+                run!("touch", argument);
+                assert!(PathBuf::from("filename with spaces").exists());
+            });
+        }
+    }
+
+    mod display_arguments {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn integers() {
+            let StdoutTrimmed(output) =
+                run_output!("echo", 1u16, -2i16, 3u32, -4i64, 5u128, 6usize, -7isize);
+            assert_eq!(output, "1 -2 3 -4 5 6 -7");
+        }
+
+        #[test]
+        fn bytes_with_arg() {
+            let StdoutTrimmed(output) = run_output!("echo", Arg(1u8));
+            assert_eq!(output, "1");
+        }
+
+        #[test]
+        fn integer_literals() {
+            let StdoutTrimmed(output) = run_output!("head", "-n", 1, Stdin("foo\nbar\n"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn floats() {
+            let StdoutTrimmed(output) = run_output!("echo", 1.5f32, 0.25f64);
+            assert_eq!(output, "1.5 0.25");
+        }
+
+        #[test]
+        fn chars() {
+            let StdoutTrimmed(output) = run_output!("echo", 'x', 'ä');
+            assert_eq!(output, "x ä");
+        }
+
+        #[test]
+        fn references_to_numbers() {
+            let n = 42;
+            let StdoutTrimmed(output) = run_output!("echo", &n);
+            assert_eq!(output, "42");
+        }
+
+        #[test]
+        fn each_number_is_one_argument() {
+            let StdoutUntrimmed(output) = run_output!("printf", "%s\n", 1, 2.5, 'c');
+            assert_eq!(output, "1\n2.5\nc\n");
+        }
+
+        #[test]
+        fn arg_uses_display() {
+            let StdoutTrimmed(output) = run_output!(
+                "echo",
+                Arg(std::net::Ipv4Addr::LOCALHOST),
+                Arg(format_args!("{}-{}", "foo", 42).to_string())
+            );
+            assert_eq!(output, "127.0.0.1 foo-42");
+        }
+
+        #[test]
+        fn arg_with_whitespace_is_one_argument() {
+            let StdoutUntrimmed(output) = run_output!("printf", "[%s]", Arg("foo bar"));
+            assert_eq!(output, "[foo bar]");
+        }
+    }
+
+    mod os_strings {
+        use super::*;
+
+        #[test]
+        fn works_for_os_string() {
+            run!(OsString::from("true"));
+        }
+
+        #[test]
+        fn works_for_os_str() {
+            run!(OsStr::new("true"));
+        }
+    }
+
+    mod stdout {
+        use super::*;
+        use std::{thread, time::Duration};
+
+        #[test]
+        fn relays_stdout_by_default() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stdout(), "foo\n");
+        }
+
+        #[test]
+        fn relays_stdout_for_non_zero_exit_codes() {
+            let context = Context::test();
+            let _ = run_result_with_context_unit(
+                context.clone(),
+                (test_helper(), "output foo and exit with 42"),
+            );
+            assert_eq!(context.stdout(), "foo\n");
+        }
+
+        #[test]
+        fn streams_stdout() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                let context_clone = context.clone();
+                let thread = thread::spawn(|| {
+                    run_result_with_context_unit(
+                        context_clone,
+                        (test_helper(), "stream chunk then wait for file"),
+                    )
+                    .unwrap();
+                });
+                while (context.stdout()) != "foo\n" {
+                    thread::sleep(Duration::from_secs_f32(0.05));
+                }
+                run!(%"touch file");
+                thread.join().unwrap();
+            });
+        }
+
+        #[test]
+        fn does_not_relay_stdout_when_collecting_into_string() {
+            let context = Context::test();
+            let StdoutTrimmed(_) =
+                run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn does_not_relay_stdout_when_collecting_into_result_of_string() {
+            let context = Context::test();
+            let _: Result<StdoutTrimmed, Error> =
+                run_result_with_context(context.clone(), Split("echo foo"));
+            assert_eq!(context.stdout(), "");
+        }
+    }
+
+    mod parsed {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::net::Ipv4Addr;
+
+        #[test]
+        fn parses_stdout() {
+            let Parsed(number): Parsed<i32> = run_output!(%"echo -42");
+            assert_eq!(number, -42);
+        }
+
+        #[test]
+        fn trims_stdout_before_parsing() {
+            let Parsed(address): Parsed<Ipv4Addr> = run_output!("echo", "  127.0.0.1  ");
+            assert_eq!(address, Ipv4Addr::LOCALHOST);
+        }
+
+        #[test]
+        fn does_not_relay_stdout() {
+            let context = Context::test();
+            let Parsed(_): Parsed<u8> =
+                run_result_with_context(context.clone(), Split("echo 42")).unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn parse_errors() {
+            let result: Result<Parsed<u8>, Error> = run_result!(%"echo 256");
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "echo 256:\n  could not parse stdout \"256\": number too large to fit in target type"
+            );
+            match error {
+                Error::ParseStdout { stdout, .. } => assert_eq!(stdout, "256"),
+                error => panic!("unexpected error: {:?}", error),
+            }
+        }
+
+        #[test]
+        fn parse_errors_have_a_source() {
+            let result: Result<Parsed<u8>, Error> = run_result!(%"echo foo");
+            assert!(std::error::Error::source(&result.unwrap_err()).is_some());
+        }
+
+        #[test]
+        #[should_panic(expected = "could not parse stdout")]
+        fn run_output_panics_on_parse_errors() {
+            let Parsed(_): Parsed<u8> = run_output!(%"echo foo");
+        }
+    }
+
+    mod lines {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn splits_stdout_into_lines() {
+            let StdoutLines(lines) = run_output!("printf", "foo\nbar\nbaz");
+            assert_eq!(lines, vec!["foo", "bar", "baz"]);
+        }
+
+        #[test]
+        fn drops_the_trailing_empty_line() {
+            let StdoutLines(lines) = run_output!("printf", "foo\n");
+            assert_eq!(lines, vec!["foo"]);
+        }
+
+        #[test]
+        fn handles_carriage_returns() {
+            let StdoutLines(lines) = run_output!("printf", "foo\r\nbar\r\n");
+            assert_eq!(lines, vec!["foo", "bar"]);
+        }
+
+        #[test]
+        fn keeps_inner_empty_lines() {
+            let StdoutLines(lines) = run_output!("printf", "foo\n\nbar\n");
+            assert_eq!(lines, vec!["foo", "", "bar"]);
+        }
+
+        #[test]
+        fn empty_stdout_results_in_no_lines() {
+            let StdoutLines(lines) = run_output!("true");
+            assert_eq!(lines, Vec::<String>::new());
+        }
+
+        #[test]
+        fn does_not_relay_stdout() {
+            let context = Context::test();
+            let StdoutLines(_) =
+                run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn splits_stderr_into_lines() {
+            let StderrLines(lines) = run_output!("sh", "-c", "printf 'foo\\r\\nbar\\n' >&2");
+            assert_eq!(lines, vec!["foo", "bar"]);
+        }
+
+        #[test]
+        fn streams_stdout_lines_of_handles() {
+            let mut handle: Handle<StdoutStream> = spawn!("printf", "foo\r\nbar\n\nbaz").unwrap();
+            let lines = handle
+                .stdout_lines()
+                .unwrap()
+                .collect::<Result<Vec<String>, Error>>()
+                .unwrap();
+            assert_eq!(lines, vec!["foo", "bar", "", "baz"]);
+            handle.wait().unwrap();
+        }
+
+        #[test]
+        fn streams_lines_while_the_child_is_running() {
+            in_temporary_directory(|| {
+                let mut handle: Handle<StdoutStream> =
+                    spawn!(test_helper(), "stream chunk then wait for file").unwrap();
+                let mut lines = handle.stdout_lines().unwrap();
+                assert_eq!(lines.next().unwrap().unwrap(), "foo");
+                run!(%"touch file");
+                assert!(lines.next().is_none());
+                handle.wait().unwrap();
+            });
+        }
+
+        #[test]
+        fn streamed_stdout_is_not_relayed() {
+            let context = Context::test();
+            let mut handle: Handle<StdoutStream> =
+                crate::input::spawn_with_context(context.clone(), Split("echo foo")).unwrap();
+            let lines = handle.stdout_lines().unwrap().count();
+            handle.wait().unwrap();
+            assert_eq!(lines, 1);
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn stdout_lines_are_only_available_with_stdout_stream() {
+            let mut handle: Handle<()> = spawn!("true").unwrap();
+            assert!(handle.stdout_lines().is_none());
+            handle.wait().unwrap();
+        }
+
+        #[test]
+        fn stdout_lines_can_only_be_taken_once() {
+            let mut handle: Handle<StdoutStream> = spawn!("true").unwrap();
+            assert!(handle.stdout_lines().is_some());
+            assert!(handle.stdout_lines().is_none());
+            handle.wait().unwrap();
+        }
+
+        #[test]
+        fn invalid_utf8_in_streamed_lines() {
+            let mut handle: Handle<StdoutStream> = spawn!("printf", "\\377\\n").unwrap();
+            let result = handle.stdout_lines().unwrap().next().unwrap();
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "printf \\377\\n:\n  invalid utf-8 written to stdout"
+            );
+            handle.wait().unwrap();
+        }
+
+        #[test]
+        fn discards_unreceived_stdout_with_run_output() {
+            let StdoutStream = run_output!("sh", "-c", "yes | head -n 100000");
+        }
+
+        #[test]
+        fn discards_stdout_when_lines_are_not_taken() {
+            let handle: Handle<StdoutStream> = spawn!("sh", "-c", "yes | head -n 100000").unwrap();
+            handle.wait().unwrap();
+        }
+
+        #[test]
+        fn discards_stdout_after_the_lines_are_dropped() {
+            let mut handle: Handle<StdoutStream> =
+                spawn!("sh", "-c", "yes | head -n 100000").unwrap();
+            let mut lines = handle.stdout_lines().unwrap();
+            assert_eq!(lines.next().unwrap().unwrap(), "y");
+            drop(lines);
+            handle.wait().unwrap();
+        }
+    }
+
+    mod nul_separated {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn splits_stdout_on_nul_bytes() {
+            let StdoutNulSeparated(records) = run_output!("printf", "foo\\0bar baz\\0");
+            assert_eq!(
+                records,
+                vec![OsString::from("foo"), OsString::from("bar baz")]
+            );
+        }
+
+        #[test]
+        fn keeps_the_last_record_without_a_trailing_nul_byte() {
+            let StdoutNulSeparated(records) = run_output!("printf", "foo\\0bar");
+            assert_eq!(records, vec![OsString::from("foo"), OsString::from("bar")]);
+        }
+
+        #[test]
+        fn empty_stdout_results_in_no_records() {
+            let StdoutNulSeparated(records) = run_output!("true");
+            assert_eq!(records, Vec::<OsString>::new());
+        }
+
+        #[test]
+        fn keeps_newlines_in_records() {
+            let StdoutNulSeparated(records) = run_output!("printf", "foo\\nbar\\0");
+            assert_eq!(records, vec![OsString::from("foo\nbar")]);
+        }
+
+        #[test]
+        fn writes_nul_separated_stdin() {
+            let StdoutUntrimmed(output) = run_output!(
+                test_helper(),
+                "reverse",
+                StdinNulSeparated(vec!["foo", "bar"])
+            );
+            assert_eq!(output, "\0rab\0oof");
+        }
+
+        #[test]
+        fn can_be_combined_with_stdin() {
+            let StdoutUntrimmed(output) = run_output!(
+                "cat",
+                Stdin("foo\n"),
+                StdinNulSeparated(["bar"]),
+                Stdin("baz")
+            );
+            assert_eq!(output, "foo\nbar\0baz");
+        }
+
+        #[test]
+        fn round_trips_file_names() {
+            in_temporary_directory(|| {
+                run!("touch", "foo bar", "baz\nqux");
+                let StdoutNulSeparated(mut files) = run_output!(%"find . -type f -print0");
+                files.sort();
+                let StdoutNulSeparated(mut output) = run_output!(
+                    %"xargs -0 printf %s\\0",
+                    StdinNulSeparated(&files)
+                );
+                output.sort();
+                assert_eq!(output, files);
+                assert_eq!(
+                    files,
+                    vec![OsString::from("./baz\nqux"), OsString::from("./foo bar")]
+                );
+            });
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn works_for_non_utf8_file_names() {
+            use std::os::unix::ffi::OsStrExt;
+            let name = OsStr::from_bytes(&[b'f', 0xff, b'o']);
+            let StdoutNulSeparated(records) =
+                run_output!("cat", StdinNulSeparated(vec![name, OsStr::new("bar")]));
+            assert_eq!(records, vec![name.to_os_string(), OsString::from("bar")]);
+            let result: Result<StdoutUntrimmed, Error> =
+                run_result!("cat", StdinNulSeparated(vec![name]));
+            assert!(result.is_err());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    mod json {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use serde_crate::Deserialize;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(crate = "serde_crate")]
+        struct Person {
+            name: String,
+            age: u32,
+        }
+
+        #[test]
+        fn deserializes_stdout() {
+            let Json(person): Json<Person> = run_output!("echo", r#"{"name": "foo", "age": 42}"#);
+            assert_eq!(
+                person,
+                Person {
+                    name: "foo".to_string(),
+                    age: 42
+                }
+            );
+        }
+
+        #[test]
+        fn does_not_relay_stdout() {
+            let context = Context::test();
+            let Json(_): Json<u32> =
+                run_result_with_context(context.clone(), Split("echo 42")).unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn errors_contain_the_command_and_a_snippet() {
+            let result: Result<Json<Person>, Error> =
+                run_result!("echo", r#"{"name": "foo", "age": "42"}"#);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                unindent::unindent(
+                    r#"
+                    echo '{"name": "foo", "age": "42"}':
+                      could not parse stdout as json: invalid type: string "42", expected u32 at line 1 column 27
+                      {"name": "foo", "age": "42"}"#
+                )
+            );
+        }
+
+        #[test]
+        fn errors_have_a_source() {
+            let result: Result<Json<u32>, Error> = run_result!(%"echo foo");
+            assert!(std::error::Error::source(&result.unwrap_err()).is_some());
+        }
+
+        #[test]
+        fn deserializes_json_lines() {
+            let JsonLines(people): JsonLines<Person> = run_output!(
+                "printf",
+                r#"{"name": "foo", "age": 1}\n\n{"name": "bar", "age": 2}\n"#
+            );
+            assert_eq!(
+                people,
+                vec![
+                    Person {
+                        name: "foo".to_string(),
+                        age: 1
+                    },
+                    Person {
+                        name: "bar".to_string(),
+                        age: 2
+                    }
+                ]
+            );
+        }
+
+        #[test]
+        fn json_lines_errors_contain_the_offending_line() {
+            let result: Result<JsonLines<u32>, Error> = run_result!("printf", "1\n2\nfoo\n4\n");
+            match result.unwrap_err() {
+                Error::ParseJson { snippet, .. } => assert_eq!(snippet, "foo"),
+                error => panic!("unexpected error: {:?}", error),
+            }
+        }
+    }
+
+    mod stderr {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::{thread, time::Duration};
+
+        #[test]
+        fn relays_stderr_by_default() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (test_helper(), "write to stderr"))
+                .unwrap();
+            assert_eq!(context.stderr(), "foo\n");
+        }
+
+        #[test]
+        fn relays_stderr_for_non_zero_exit_codes() {
+            let context = Context::test();
+            let _: Result<(), Error> = run_result_with_context(
+                context.clone(),
+                (test_helper(), "write to stderr and exit with 42"),
+            );
+            assert_eq!(context.stderr(), "foo\n");
+        }
+
+        #[test]
+        fn streams_stderr() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                let context_clone = context.clone();
+                let thread = thread::spawn(|| {
+                    run_result_with_context_unit(
+                        context_clone,
+                        (test_helper(), "stream chunk to stderr then wait for file"),
+                    )
+                    .unwrap();
+                });
+                loop {
+                    let expected = "foo\n";
+                    let stderr = context.stderr();
+                    if stderr == expected {
+                        break;
+                    }
+                    assert!(
+                        stderr.len() <= expected.len(),
+                        "expected: {}, got: {}",
+                        expected,
+                        stderr
+                    );
+                    thread::sleep(Duration::from_secs_f32(0.05));
+                }
+                run!(%"touch file");
+                thread.join().unwrap();
+            });
+        }
+
+        #[test]
+        fn capture_stderr() {
+            let Stderr(stderr) = run_output!(test_helper(), "write to stderr");
+            assert_eq!(stderr, "foo\n");
+        }
+
+        #[test]
+        fn assumes_stderr_is_utf_8() {
+            let result: Result<Stderr, Error> = run_result!(test_helper(), "invalid utf-8 stderr");
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "{} 'invalid utf-8 stderr':\n  invalid utf-8 written to stderr",
+                    test_helper().display(),
+                )
+            );
+        }
+
+        #[test]
+        #[cfg(not(windows))]
+        fn does_allow_invalid_utf_8_to_stderr_when_not_captured() {
+            run!(test_helper(), "invalid utf-8 stderr");
+        }
+
+        #[test]
+        fn does_not_relay_stderr_when_catpuring() {
+            let context = Context::test();
+            let Stderr(_) =
+                run_result_with_context(context.clone(), (test_helper(), "write to stderr"))
+                    .unwrap();
+            assert_eq!(context.stderr(), "");
+        }
+    }
+
+    mod conditional_inputs {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn some_is_used_as_input() {
+            let StdoutTrimmed(output) = run_output!("echo", Some("foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn none_contributes_nothing() {
+            let StdoutTrimmed(output) = run_output!("echo", None::<&str>, "foo");
+            assert_eq!(output, "foo");
+        }
+
+        #[rustversion::since(1.50)]
+        #[test]
+        #[allow(clippy::unnecessary_lazy_evaluations)]
+        fn works_with_bool_then() {
+            let StdoutTrimmed(output) =
+                run_output!(%"echo foo", true.then(|| "bar"), false.then(|| "baz"));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn works_for_non_argument_inputs() {
+            let StdoutTrimmed(output) = run_output!("cat", Some(Stdin("foo")));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn when_true_uses_the_input() {
+            let StdoutTrimmed(output) = run_output!("echo", When(true, ("foo", "bar")));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn when_false_contributes_nothing() {
+            let StdoutTrimmed(output) = run_output!("echo", When(false, "foo"), "bar");
+            assert_eq!(output, "bar");
+        }
+
+        #[test]
+        fn options_can_be_nested_in_tuples() {
+            let StdoutTrimmed(output) = run_output!(("echo", Some(Split("foo bar"))));
+            assert_eq!(output, "foo bar");
+        }
+    }
+
+    mod log_commands {
+        use super::*;
+
+        #[test]
+        fn logs_simple_commands() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, "true")).unwrap();
+            assert_eq!(context.stderr(), "+ true\n");
+        }
+
+        #[test]
+        fn logs_commands_with_arguments() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, Split("echo foo"))).unwrap();
+            assert_eq!(context.stderr(), "+ echo foo\n");
+        }
+
+        #[test]
+        fn quotes_arguments_with_spaces() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "foo bar")).unwrap();
+            assert_eq!(context.stderr(), "+ echo 'foo bar'\n");
+        }
+
+        #[test]
+        fn quotes_empty_arguments() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "")).unwrap();
+            assert_eq!(context.stderr(), "+ echo ''\n");
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn arguments_with_invalid_utf8_will_be_logged_with_lossy_conversion() {
+            use std::{ffi::OsStr, os::unix::prelude::OsStrExt, path::Path};
+            let context = Context::test();
+            let argument_with_invalid_utf8: &OsStr =
+                OsStrExt::from_bytes(&[102, 111, 111, 0x80, 98, 97, 114]);
+            let argument_with_invalid_utf8: &Path = argument_with_invalid_utf8.as_ref();
+            run_result_with_context_unit(
+                context.clone(),
+                (LogCommand, "echo", argument_with_invalid_utf8),
+            )
+            .unwrap();
+            assert_eq!(context.stderr(), "+ echo foo�bar\n");
+        }
+    }
+
+    mod scoped_defaults {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn applies_defaults_inside_the_scope() {
+            let context = Context::test();
+            with_defaults(LogCommand, || {
+                run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
+            });
+            assert_eq!(context.stderr(), "+ echo foo\n");
+        }
+
+        #[test]
+        fn removes_defaults_after_the_scope() {
+            with_defaults(LogCommand, || {});
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn returns_the_result_of_the_closure() {
+            let StdoutTrimmed(output) = with_defaults(
+                Env("FOO", "bar"),
+                || run_output!(test_helper(), %"echo FOO"),
+            );
+            assert_eq!(output, "bar");
+        }
+
+        #[test]
+        fn inputs_of_the_call_override_defaults() {
+            with_defaults(Env("FOO", "default"), || {
+                let StdoutTrimmed(output) =
+                    run_output!(test_helper(), %"echo FOO", Env("FOO", "override"));
+                assert_eq!(output, "override");
+            });
+        }
+
+        #[test]
+        fn default_arguments_are_prepended() {
+            with_defaults("echo", || {
+                let StdoutTrimmed(output) = run_output!(%"foo bar");
+                assert_eq!(output, "foo bar");
+            });
+        }
+
+        #[test]
+        fn scopes_can_be_nested() {
+            with_defaults(Env("FOO", "foo"), || {
+                with_defaults(Env("BAR", "bar"), || {
+                    let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO $BAR");
+                    assert_eq!(output, "foo bar");
+                });
+                let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO $BAR");
+                assert_eq!(output, "foo");
+            });
+        }
+
+        #[test]
+        fn guards_remove_defaults_when_dropped() {
+            let context = Context::test();
+            let guard = scoped_defaults(LogCommand);
+            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
+            drop(guard);
+            run_result_with_context_unit(context.clone(), Split("echo bar")).unwrap();
+            assert_eq!(context.stderr(), "+ echo foo\n");
+        }
+
+        #[test]
+        fn guards_dropped_out_of_order_remove_all_later_defaults() {
+            let context = Context::test();
+            let outer = scoped_defaults(LogCommand);
+            let inner = scoped_defaults(Env("FOO", "foo"));
+            drop(outer);
+            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
+            drop(inner);
+            run_result_with_context_unit(context.clone(), Split("echo bar")).unwrap();
+            assert_eq!(context.stderr(), "");
+            let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO");
+            assert_eq!(output, "");
+        }
+
+        #[test]
+        fn defaults_are_removed_when_the_closure_panics() {
+            let result = std::panic::catch_unwind(|| {
+                with_defaults(LogCommand, || panic!("test panic"));
+            });
+            assert!(result.is_err());
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn defaults_apply_to_handles() {
+            let dir = TempDir::new().unwrap();
+            let path: PathBuf = dir.path().into();
+            with_defaults(CurrentDir(path), || {
+                let handle: Handle<()> = spawn!(%"touch foo").unwrap();
+                handle.wait().unwrap();
+            });
+            assert!(dir.path().join("foo").exists());
+        }
+
+        #[test]
+        fn defaults_do_not_apply_to_other_threads() {
+            with_defaults(Env("FOO", "foo"), || {
+                let StdoutTrimmed(output) =
+                    std::thread::spawn(|| run_output!(%"sh -c", "echo $FOO"))
+                        .join()
+                        .unwrap();
+                assert_eq!(output, "");
+            });
+        }
+    }
+
+    mod secrets {
+        use super::*;
+        use crate::config::Config;
+        use pretty_assertions::assert_eq;
+
+        fn config(input: impl Input) -> Config {
+            let mut config = Config::default();
+            input.configure(&mut config);
+            config
+        }
+
+        #[test]
+        fn passes_secrets_unchanged() {
+            let StdoutTrimmed(output) = run_output!("echo", Secret("secret-token"));
+            assert_eq!(output, "secret-token");
+        }
+
+        #[test]
+        fn redacts_secrets_in_logged_commands() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (LogCommand, "echo", Secret("secret-token"), "foo"),
+            )
+            .unwrap();
+            assert_eq!(context.stderr(), "+ echo *** foo\n");
+            assert_eq!(context.stdout(), "secret-token foo\n");
+        }
+
+        #[test]
+        fn redacts_secrets_in_error_messages() {
+            let result: Result<(), Error> = run_result!("false", Secret("secret token"));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "false ***:\n  exited with exit code: 1"
+            );
+        }
+
+        #[test]
+        fn redacts_all_arguments_added_by_the_input() {
+            assert_eq!(
+                config(("curl", Secret(Split("-u user:password")), "url")).full_command(),
+                "curl *** *** url"
+            );
+        }
+
+        #[test]
+        fn redacts_secrets_in_debug_output() {
+            let debug = format!("{:?}", config(("echo", Secret("secret-token"))));
+            assert!(!debug.contains("secret-token"), "{}", debug);
+            assert!(debug.contains(r#"arguments: ["echo", "***"]"#), "{}", debug);
+        }
+
+        #[test]
+        fn redacts_secret_environment_variable_values() {
+            let config = config((Env("FOO", "foo"), Secret(Env("TOKEN", "secret-token"))));
+            let debug = format!("{:?}", config);
+            assert!(!debug.contains("secret-token"), "{}", debug);
+            assert!(
+                debug
+                    .contains(r#"added_environment_variables: [("FOO", "foo"), ("TOKEN", "***")]"#),
+                "{}",
+                debug
+            );
+        }
+
+        #[test]
+        fn passes_secret_environment_variables_unchanged() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                %"echo TOKEN",
+                Secret(Env("TOKEN", "secret-token"))
+            );
+            assert_eq!(output, "secret-token");
+        }
+
+        #[test]
+        fn redacts_secrets_in_internal_errors() {
+            let config = config(("echo", Secret("secret-token")));
+            let error = Error::internal("test", &config);
+            assert!(!error.to_string().contains("secret-token"), "{}", error);
+            assert!(
+                !format!("{:?}", error).contains("secret-token"),
+                "{:?}",
+                error
+            );
+        }
+
+        #[test]
+        fn redacts_secrets_in_their_own_debug_output() {
+            let debug = format!("{:?}", Secret(Env("TOKEN", "secret-token")));
+            assert_eq!(debug, "Secret(***)");
+        }
+
+        #[test]
+        fn redacts_secret_stdin_in_debug_output() {
+            let config = config(("cat", Stdin("foo"), Secret(Stdin("secret-token"))));
+            let debug = format!("{:?}", config);
+            assert!(!debug.contains("secret-token"), "{}", debug);
+            assert!(debug.contains(r#"stdin: Some("***")"#), "{}", debug);
+        }
+
+        #[test]
+        fn keeps_secret_paths_secret_when_modifying_them() {
+            let config = config((
+                Secret(Env("PATH", "/secret/bin")),
+                PathPrepend("/foo"),
+                PathAppend("/bar"),
+            ));
+            let debug = format!("{:?}", config);
+            assert!(!debug.contains("/secret/bin"), "{}", debug);
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn does_not_redact_modified_paths_that_are_not_secret() {
+            let config = config((Env("PATH", "/bin"), PathPrepend("/foo")));
+            let debug = format!("{:?}", config);
+            assert!(debug.contains("/foo:/bin"), "{}", debug);
+        }
+    }
+
+    mod exit_status {
+        use super::*;
+
+        #[test]
+        fn zero() {
+            let Status(exit_status) = run_output!("true");
+            assert!(exit_status.success());
+        }
+
+        #[test]
+        fn one() {
+            let Status(exit_status) = run_output!("false");
+            assert!(!exit_status.success());
+        }
+
+        #[test]
+        fn forty_two() {
+            let Status(exit_status) = run_output!(test_helper(), "exit code 42");
+            assert!(!exit_status.success());
+            assert_eq!(exit_status.code(), Some(42));
+        }
+
+        #[test]
+        fn failing_commands_return_oks_when_exit_status_is_captured() {
+            let Status(exit_status) = run_result!("false").unwrap();
+            assert!(!exit_status.success());
+        }
+    }
+
+    mod exit {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn zero() {
+            let exit: Exit = run_output!("true");
+            assert_eq!(exit, Exit::Code(0));
+            assert!(exit.success());
+        }
+
+        #[test]
+        fn forty_two() {
+            let exit: Exit = run_output!(test_helper(), "exit code 42");
+            assert_eq!(exit, Exit::Code(42));
+            assert!(!exit.success());
+        }
+
+        #[test]
+        fn failing_commands_return_oks_when_exit_is_captured() {
+            let exit: Exit = run_result!("false").unwrap();
+            assert_eq!(exit, Exit::Code(1));
+        }
+
+        #[test]
+        fn non_zero_exit_code_errors_contain_exit() {
+            let result: Result<(), Error> = run_result!(test_helper(), "exit code 42");
+            match result {
+                Err(Error::NonZeroExitCode { exit, .. }) => assert_eq!(exit, Exit::Code(42)),
+                _ => panic!("should match Error::NonZeroExitCode"),
+            }
+        }
+
+        #[cfg(unix)]
+        mod signals {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn signals() {
+                let exit: Exit = run_output!("sh", "-c", "kill -9 $$");
+                assert_eq!(
+                    exit,
+                    Exit::Signal {
+                        signal: Signal::SIGKILL as i32,
+                        core_dumped: false,
+                    }
+                );
+                assert!(!exit.success());
+            }
+
+            #[test]
+            fn non_zero_exit_code_errors_contain_signals() {
+                let result: Result<(), Error> = run_result!("sh", "-c", "kill -TERM $$");
+                match result {
+                    Err(Error::NonZeroExitCode {
+                        exit: Exit::Signal { signal, .. },
+                        ..
+                    }) => assert_eq!(signal, Signal::SIGTERM as i32),
+                    _ => panic!("should match Error::NonZeroExitCode"),
+                }
+            }
+
+            #[test]
+            fn displays_signal_names() {
+                let exit = Exit::Signal {
+                    signal: Signal::SIGKILL as i32,
+                    core_dumped: false,
+                };
+                assert_eq!(exit.to_string(), "killed by SIGKILL");
+            }
+
+            #[test]
+            fn displays_core_dumps() {
+                let exit = Exit::Signal {
+                    signal: Signal::SIGSEGV as i32,
+                    core_dumped: true,
+                };
+                assert_eq!(exit.to_string(), "killed by SIGSEGV (core dumped)");
+            }
+        }
+    }
+
+    mod bool_output {
+        use super::*;
+
+        #[test]
+        fn success_exit_status_is_true() {
+            assert!(run_output!("true"));
+        }
+
+        #[test]
+        fn failure_exit_status_is_false() {
+            assert!(!run_output!("false"));
+        }
+
+        #[test]
+        #[should_panic]
+        fn io_error_panics() {
+            assert!(run_output!("/"));
+        }
+    }
+
+    mod tuple_inputs {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn two_tuple() {
+            let StdoutTrimmed(output) = run_output!(("echo", "foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn three_tuples() {
+            let StdoutTrimmed(output) = run_output!(("echo", "foo", "bar"));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn nested_tuples() {
+            let StdoutTrimmed(output) = run_output!(("echo", ("foo", "bar")));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn unit_input() {
+            let StdoutTrimmed(output) = run_output!(("echo", ()));
+            assert_eq!(output, "");
+        }
+    }
+
+    mod tuple_outputs {
+        use super::*;
+
+        #[test]
+        fn two_tuple_1() {
+            let (StdoutTrimmed(output), Status(exit_status)) =
+                run_output!(test_helper(), "output foo and exit with 42");
+            assert_eq!(output, "foo");
+            assert_eq!(exit_status.code(), Some(42));
+        }
+
+        #[test]
+        fn two_tuple_2() {
+            let (Status(exit_status), StdoutTrimmed(output)) =
+                run_output!(test_helper(), "output foo and exit with 42");
+            assert_eq!(output, "foo");
+            assert_eq!(exit_status.code(), Some(42));
+        }
+
+        #[test]
+        fn result_of_tuple() {
+            let (StdoutTrimmed(output), Status(exit_status)) = run_result!(%"echo foo").unwrap();
+            assert_eq!(output, "foo");
+            assert!(exit_status.success());
+        }
+
+        #[test]
+        fn result_of_tuple_when_erroring() {
+            let (StdoutTrimmed(output), Status(exit_status)) = run_result!("false").unwrap();
+            assert_eq!(output, "");
+            assert_eq!(exit_status.code(), Some(1));
+        }
+
+        #[test]
+        fn three_tuples() {
+            let (Stderr(stderr), StdoutTrimmed(stdout), Status(exit_status)) =
+                run_output!(%"echo foo");
+            assert_eq!(stderr, "");
+            assert_eq!(stdout, "foo");
+            assert_eq!(exit_status.code(), Some(0));
+        }
+
+        #[test]
+        fn capturing_stdout_on_errors() {
+            let (StdoutTrimmed(output), Status(exit_status)) =
+                run_output!(test_helper(), "output foo and exit with 42");
+            assert!(!exit_status.success());
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn capturing_stderr_on_errors() {
+            let (Stderr(output), Status(exit_status)) =
+                run_output!(test_helper(), "write to stderr and exit with 42");
+            assert!(!exit_status.success());
+            assert_eq!(output, "foo\n");
+        }
+    }
+
+    mod current_dir {
+        use super::*;
+        use std::path::Path;
+
+        #[test]
+        fn sets_the_working_directory() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                fs::write("dir/file", "foo").unwrap();
+                fs::write("file", "wrong file").unwrap();
+                let StdoutUntrimmed(output) = run_output!(%"cat file", CurrentDir("dir"));
+                assert_eq!(output, "foo");
+            });
+        }
+
+        #[test]
+        fn works_for_other_types() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                let dir: String = "dir".to_string();
+                run!("true", CurrentDir(dir));
+                let dir: PathBuf = PathBuf::from("dir");
+                run!("true", CurrentDir(dir));
+                let dir: &Path = Path::new("dir");
+                run!("true", CurrentDir(dir));
+            });
+        }
+    }
+
+    mod capturing_stdout {
+        use super::*;
+
+        mod trimmed {
+            use super::*;
+
+            #[test]
+            fn trims_trailing_whitespace() {
+                let StdoutTrimmed(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo");
+            }
+
+            #[test]
+            fn trims_leading_whitespace() {
+                let StdoutTrimmed(output) = run_output!(%"echo -n", " foo");
+                assert_eq!(output, "foo");
+            }
+
+            #[test]
+            fn does_not_remove_whitespace_within_output() {
+                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo bar");
+                assert_eq!(output, "foo bar");
+            }
+
+            #[test]
+            fn does_not_modify_output_without_whitespace() {
+                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo");
+                assert_eq!(output, "foo");
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let StdoutTrimmed(_) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+
+        mod untrimmed {
+            use super::*;
+
+            #[test]
+            fn does_not_trim_trailing_newline() {
+                let StdoutUntrimmed(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn does_not_trim_leading_whitespace() {
+                let StdoutUntrimmed(output) = run_output!(%"echo -n", " foo");
+                assert_eq!(output, " foo");
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let StdoutUntrimmed(_) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+    }
+
+    mod split {
+        use super::*;
+
+        #[test]
+        fn splits_words_by_whitespace() {
+            let StdoutTrimmed(output) = run_output!(Split("echo foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn skips_multiple_whitespace_characters() {
+            let StdoutUntrimmed(output) = run_output!("echo", Split("foo  bar"));
+            assert_eq!(output, "foo bar\n");
+        }
+
+        #[test]
+        fn trims_leading_whitespace() {
+            let StdoutTrimmed(output) = run_output!(Split(" echo foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn trims_trailing_whitespace() {
+            let StdoutUntrimmed(output) = run_output!("echo", Split("foo "));
+            assert_eq!(output, "foo\n");
+        }
+
+        mod percent_sign {
+            use super::*;
+
+            #[test]
+            fn splits_words() {
+                let StdoutUntrimmed(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn works_for_later_arguments() {
+                let StdoutUntrimmed(output) = run_output!("echo", %"foo\tbar");
+                assert_eq!(output, "foo bar\n");
+            }
+
+            #[test]
+            fn for_first_of_multiple_arguments() {
+                let StdoutUntrimmed(output) = run_output!(%"echo foo", "bar");
+                assert_eq!(output, "foo bar\n");
+            }
+
+            #[test]
+            fn non_literals() {
+                let command = "echo foo";
+                let StdoutUntrimmed(output) = run_output!(%command);
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn in_run() {
+                run!(%"echo foo");
+            }
+
+            #[test]
+            fn in_run_result() {
+                let StdoutTrimmed(_) = run_result!(%"echo foo").unwrap();
+            }
+        }
+    }
+
+    mod splitting_with_library_functions {
+        use super::*;
+
+        #[test]
+        fn allow_to_use_split() {
+            let StdoutTrimmed(output) = run_output!("echo foo".split(' '));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn split_whitespace() {
+            let StdoutTrimmed(output) = run_output!("echo foo".split_whitespace());
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn split_ascii_whitespace() {
+            let StdoutTrimmed(output) = run_output!("echo foo".split_ascii_whitespace());
+            assert_eq!(output, "foo");
+        }
+    }
+
+    mod paths {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::path::Path;
+
+        fn write_test_script() -> PathBuf {
+            if cfg!(unix) {
+                let file = PathBuf::from("./test-script");
+                let script = "#!/usr/bin/env bash\necho test-output\n";
+                fs::write(&file, script).unwrap();
+                run!(%"chmod +x test-script");
+                file
+            } else {
+                let file = PathBuf::from("./test-script.bat");
+                let script = "@echo test-output\n";
+                fs::write(&file, script).unwrap();
+                file
+            }
+        }
+
+        #[test]
+        fn ref_path_as_argument() {
+            in_temporary_directory(|| {
+                let file: &Path = Path::new("file");
+                fs::write(file, "test-contents").unwrap();
+                let StdoutUntrimmed(output) = run_output!("cat", file);
+                assert_eq!(output, "test-contents");
+            })
+        }
+
+        #[test]
+        fn ref_path_as_executable() {
+            in_temporary_directory(|| {
+                let file: &Path = &write_test_script();
+                let StdoutTrimmed(output) = run_output!(file);
+                assert_eq!(output, "test-output");
+            })
+        }
+
+        #[test]
+        fn path_buf_as_argument() {
+            in_temporary_directory(|| {
+                let file: PathBuf = PathBuf::from("file");
+                fs::write(&file, "test-contents").unwrap();
+                let StdoutUntrimmed(output) = run_output!("cat", file);
+                assert_eq!(output, "test-contents");
+            })
+        }
+
+        #[test]
+        fn path_buf_as_executable() {
+            in_temporary_directory(|| {
+                let file: PathBuf = write_test_script();
+                let StdoutTrimmed(output) = run_output!(file);
+                assert_eq!(output, "test-output");
+            })
+        }
+    }
+
+    mod stdin {
+        use super::*;
+
+        #[test]
+        fn allows_to_pass_in_strings_as_stdin() {
+            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin("foo"));
+            assert_eq!(output, "oof");
+        }
+
+        #[test]
+        fn allows_passing_in_u8_slices_as_stdin() {
+            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(&[0, 1, 2]));
+            assert_eq!(output, "\x02\x01\x00");
         }
 
         #[test]
         #[cfg(unix)]
-        fn arguments_with_invalid_utf8_will_be_logged_with_lossy_conversion() {
-            use std::{ffi::OsStr, os::unix::prelude::OsStrExt, path::Path};
+        fn stdin_is_closed_by_default() {
+            let StdoutTrimmed(output) = run_output!(test_helper(), "wait until stdin is closed");
+            assert_eq!(output, "stdin is closed");
+        }
+
+        #[test]
+        fn writing_too_many_bytes_into_a_non_reading_child_may_error() {
+            let big_string = String::from_utf8(vec![b'a'; 2_usize.pow(16) + 1]).unwrap();
+            let result: Result<(), crate::Error> = run_result!("true", Stdin(big_string));
+            let message = result.unwrap_err().to_string();
+            assert!(if cfg!(unix) {
+                message == "true:\n  Broken pipe (os error 32)"
+            } else {
+                [
+                    "true:\n  The pipe is being closed. (os error 232)",
+                    "true:\n  The pipe has been ended. (os error 109)",
+                ]
+                .contains(&message.as_str())
+            });
+        }
+
+        #[test]
+        fn multiple_stdin_arguments_are_all_passed_into_the_child_process() {
+            let StdoutUntrimmed(output) =
+                run_output!(test_helper(), "reverse", Stdin("foo"), Stdin("bar"));
+            assert_eq!(output, "raboof");
+        }
+
+        #[test]
+        fn works_for_owned_strings() {
+            let argument: String = "foo".to_string();
+            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(argument));
+            assert_eq!(output, "oof");
+        }
+    }
+
+    mod inherit {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[cfg(target_os = "linux")]
+        fn child_stream(fd: u8, input: impl Input) -> String {
+            let temp_dir = TempDir::new().unwrap();
+            let file = temp_dir.path().join("file");
+            run!(
+                "sh",
+                "-c",
+                format!("echo $(readlink /proc/$$/fd/{}) > {}", fd, file.display()),
+                input
+            );
+            fs::read_to_string(file).unwrap().trim().to_string()
+        }
+
+        #[cfg(target_os = "linux")]
+        fn parent_stream(fd: u8) -> String {
+            fs::read_link(format!("/proc/self/fd/{}", fd))
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        }
+
+        #[test]
+        #[cfg(target_os = "linux")]
+        fn streams_are_not_inherited_by_default() {
+            for fd in 0..3 {
+                assert!(child_stream(fd, ()).starts_with("pipe:"));
+            }
+        }
+
+        #[test]
+        #[cfg(target_os = "linux")]
+        fn stdin_can_be_inherited() {
+            assert_eq!(child_stream(0, StdinInherit), parent_stream(0));
+            assert!(child_stream(1, StdinInherit).starts_with("pipe:"));
+        }
+
+        #[test]
+        #[cfg(target_os = "linux")]
+        fn stdout_can_be_inherited() {
+            assert_eq!(child_stream(1, StdoutInherit), parent_stream(1));
+            assert!(child_stream(2, StdoutInherit).starts_with("pipe:"));
+        }
+
+        #[test]
+        #[cfg(target_os = "linux")]
+        fn stderr_can_be_inherited() {
+            assert_eq!(child_stream(2, StderrInherit), parent_stream(2));
+            assert!(child_stream(0, StderrInherit).starts_with("pipe:"));
+        }
+
+        #[test]
+        #[cfg(target_os = "linux")]
+        fn inherit_inherits_all_streams() {
+            for fd in 0..3 {
+                assert_eq!(child_stream(fd, Inherit), parent_stream(fd));
+            }
+        }
+
+        #[test]
+        fn inherited_stdout_is_not_relayed() {
             let context = Context::test();
-            let argument_with_invalid_utf8: &OsStr =
-                OsStrExt::from_bytes(&[102, 111, 111, 0x80, 98, 97, 114]);
-            let argument_with_invalid_utf8: &Path = argument_with_invalid_utf8.as_ref();
             run_result_with_context_unit(
                 context.clone(),
-                (LogCommand, "echo", argument_with_invalid_utf8),
+                (test_helper(), "output foo and exit with 42", StdoutInherit),
             )
-            .unwrap();
-            assert_eq!(context.stderr(), "+ echo foo�bar\n");
+            .unwrap_err();
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn inherited_stderr_is_not_relayed() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    test_helper(),
+                    "write to stderr and exit with 42",
+                    StderrInherit,
+                ),
+            )
+            .unwrap_err();
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn captured_stdout_is_not_inherited() {
+            let StdoutTrimmed(output) = run_output!(%"echo foo", Inherit);
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn captured_stderr_is_not_inherited() {
+            let Stderr(stderr) = run_output!(test_helper(), "write to stderr", Inherit);
+            assert_eq!(stderr, "foo\n");
+        }
+
+        #[test]
+        fn stdin_is_written_instead_of_inherited() {
+            let StdoutUntrimmed(output) =
+                run_output!(test_helper(), "reverse", Stdin("foo"), StdinInherit);
+            assert_eq!(output, "oof");
         }
     }
 
-    mod exit_status {
+    mod invocation_syntax {
         use super::*;
 
         #[test]
-        fn zero() {
-            let Status(exit_status) = run_output!("true");
-            assert!(exit_status.success());
+        fn trailing_comma_is_accepted_after_normal_argument() {
+            run!("echo", "foo",);
+            let StdoutUntrimmed(_) = run_output!("echo", "foo",);
+            let _result: Result<(), Error> = run_result!("echo", "foo",);
         }
 
         #[test]
-        fn one() {
-            let Status(exit_status) = run_output!("false");
-            assert!(!exit_status.success());
+        fn trailing_comma_is_accepted_after_split_argument() {
+            run!("echo", %"foo",);
+            let StdoutUntrimmed(_) = run_output!("echo", %"foo",);
+            let _result: Result<(), Error> = run_result!("echo", %"foo",);
+        }
+    }
+
+    #[cfg(unix)]
+    mod which {
+        use super::*;
+        use crate::error::NotFoundReason;
+        use pretty_assertions::assert_eq;
+        use std::{os::unix::fs::PermissionsExt, path::Path};
+
+        fn write_executable(path: &Path, content: &str) {
+            fs::write(path, content).unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
         }
 
         #[test]
-        fn forty_two() {
-            let Status(exit_status) = run_output!(test_helper(), "exit code 42");
-            assert!(!exit_status.success());
-            assert_eq!(exit_status.code(), Some(42));
+        fn resolves_executables_in_path() {
+            let path = Which("sh").resolve().unwrap();
+            assert!(path.is_absolute(), "{:?}", path);
+            assert_eq!(path.file_name(), Some(OsStr::new("sh")));
+        }
+
+        #[test]
+        fn returns_names_with_separators_unchanged() {
+            assert_eq!(
+                Which("./does-not-exist").resolve().unwrap(),
+                PathBuf::from("./does-not-exist")
+            );
+        }
+
+        #[test]
+        fn returns_not_in_path_errors() {
+            match Which("does-not-exist").resolve() {
+                Err(Error::FileNotFound {
+                    executable,
+                    reason: NotFoundReason::NotInPath { searched },
+                    ..
+                }) => {
+                    assert_eq!(executable, "does-not-exist");
+                    assert_eq!(
+                        searched,
+                        std::env::split_paths(&std::env::var_os("PATH").unwrap())
+                            .collect::<Vec<PathBuf>>()
+                    );
+                }
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn honors_path_set_with_env() {
+            let temp_dir = TempDir::new().unwrap();
+            write_executable(&temp_dir.path().join("foo"), "#!/bin/sh\necho foo\n");
+            let StdoutTrimmed(output) = run_output!("foo", Env("PATH", temp_dir.path()));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn skips_files_that_are_not_executable() {
+            let first = TempDir::new().unwrap();
+            let second = TempDir::new().unwrap();
+            fs::write(first.path().join("foo"), "").unwrap();
+            write_executable(&second.path().join("foo"), "#!/bin/sh\necho foo\n");
+            let path = std::env::join_paths(vec![first.path(), second.path()]).unwrap();
+            let StdoutTrimmed(output) = run_output!("foo", Env("PATH", path));
+            assert_eq!(output, "foo");
+        }
+
+        #[cfg(target_os = "linux")]
+        #[test]
+        fn passes_the_given_executable_name_as_argv_0() {
+            let StdoutUntrimmed(output) = run_output!("cat", "/proc/self/cmdline");
+            assert_eq!(output, "cat\0/proc/self/cmdline\0");
+        }
+
+        #[test]
+        fn not_in_path_errors_list_searched_directories() {
+            let result: Result<(), Error> = run_result!("does-not-exist", Env("PATH", "/foo:/bar"));
+            match result {
+                Err(Error::FileNotFound {
+                    reason: NotFoundReason::NotInPath { searched },
+                    ..
+                }) => assert_eq!(searched, vec![PathBuf::from("/foo"), PathBuf::from("/bar")]),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn not_executable_errors() {
+            let temp_dir = TempDir::new().unwrap();
+            let file = temp_dir.path().join("foo");
+            fs::write(&file, "").unwrap();
+            let result: Result<(), Error> = run_result!("foo", Env("PATH", temp_dir.path()));
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "File not found error when executing 'foo'\n  \
+                     found '{}' in PATH, but it's not executable",
+                    file.display()
+                )
+            );
+            match error {
+                Error::FileNotFound {
+                    reason: NotFoundReason::NotExecutable { path },
+                    ..
+                } => assert_eq!(path, file),
+                error => panic!("unexpected error: {:?}", error),
+            }
+        }
+
+        #[test]
+        fn bad_interpreter_errors() {
+            let temp_dir = TempDir::new().unwrap();
+            let file = temp_dir.path().join("foo");
+            write_executable(&file, "#!/does/not/exist -x\n");
+            let result: Result<(), Error> = run_result!(&file);
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "File not found error when executing '{}'\n  \
+                     interpreter '/does/not/exist' in the shebang of '{}' not found",
+                    file.display(),
+                    file.display()
+                )
+            );
+            match error {
+                Error::FileNotFound {
+                    reason: NotFoundReason::BadInterpreter { path, interpreter },
+                    ..
+                } => {
+                    assert_eq!(path, file);
+                    assert_eq!(interpreter, "/does/not/exist");
+                }
+                error => panic!("unexpected error: {:?}", error),
+            }
+        }
+
+        #[test]
+        fn bad_interpreter_errors_for_executables_in_path() {
+            let temp_dir = TempDir::new().unwrap();
+            write_executable(&temp_dir.path().join("foo"), "#!/does/not/exist\n");
+            let result: Result<(), Error> = run_result!("foo", Env("PATH", temp_dir.path()));
+            match result {
+                Err(Error::FileNotFound {
+                    reason: NotFoundReason::BadInterpreter { .. },
+                    ..
+                }) => {}
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn redacts_secret_paths_in_not_in_path_errors() {
+            let result: Result<(), Error> = run_result!(
+                "does-not-exist",
+                Secret(Env("PATH", "/secret/foo:/secret/bar"))
+            );
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "File not found error when executing 'does-not-exist'\n  \
+                 not found in PATH, searched: '***', '***'"
+            );
+            assert!(!format!("{:?}", error).contains("/secret"), "{:?}", error);
+        }
+
+        #[test]
+        fn redacts_secret_paths_in_not_executable_errors() {
+            let temp_dir = TempDir::new().unwrap();
+            fs::write(temp_dir.path().join("foo"), "").unwrap();
+            let result: Result<(), Error> =
+                run_result!("foo", Secret(Env("PATH", temp_dir.path())));
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "File not found error when executing 'foo'\n  \
+                 found '***' in PATH, but it's not executable"
+            );
+            let path = temp_dir.path().to_string_lossy().into_owned();
+            assert!(!format!("{:?}", error).contains(&path), "{:?}", error);
+        }
+
+        #[test]
+        fn redacts_secret_paths_in_bad_interpreter_errors() {
+            let temp_dir = TempDir::new().unwrap();
+            write_executable(&temp_dir.path().join("foo"), "#!/does/not/exist\n");
+            let result: Result<(), Error> =
+                run_result!("foo", Secret(Env("PATH", temp_dir.path())));
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "File not found error when executing 'foo'\n  \
+                 interpreter '/does/not/exist' in the shebang of '***' not found"
+            );
+            let path = temp_dir.path().to_string_lossy().into_owned();
+            assert!(!format!("{:?}", error).contains(&path), "{:?}", error);
+        }
+
+        #[test]
+        fn redacts_secret_executables() {
+            let result: Result<(), Error> =
+                run_result!(Secret("secret-executable"), Env("PATH", "/foo"));
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "File not found error when executing '***'\n  \
+                 not found in PATH, searched: '/foo'"
+            );
+            assert!(
+                !format!("{:?}", error).contains("secret-executable"),
+                "{:?}",
+                error
+            );
+        }
+
+        #[test]
+        fn redacts_paths_of_secret_executables() {
+            let temp_dir = TempDir::new().unwrap();
+            let file = temp_dir.path().join("secret-executable");
+            write_executable(&file, "#!/does/not/exist\n");
+            let result: Result<(), Error> = run_result!(Secret(&file));
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "File not found error when executing '***'\n  \
+                 interpreter '/does/not/exist' in the shebang of '***' not found"
+            );
+            assert!(
+                !format!("{:?}", error).contains("secret-executable"),
+                "{:?}",
+                error
+            );
+        }
+
+        #[test]
+        fn missing_paths_are_other_errors() {
+            let result: Result<(), Error> = run_result!("./does-not-exist");
+            match result {
+                Err(Error::FileNotFound {
+                    reason: NotFoundReason::Other,
+                    ..
+                }) => {}
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    mod env_files {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn adds_variables_from_the_file() {
+            in_temporary_directory(|| {
+                fs::write(".env", "FOO=foo\nexport BAR='bar baz'\n").unwrap();
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), %"echo FOO BAR", EnvFile(".env"));
+                assert_eq!(output, "foo\nbar baz\n");
+            });
+        }
+
+        #[test]
+        fn applies_variables_in_order_with_env() {
+            in_temporary_directory(|| {
+                fs::write(".env", "FOO=from-file\nBAR=${FOO}-${BAZ}\n").unwrap();
+                let StdoutUntrimmed(output) = run_output!(
+                    test_helper(),
+                    %"echo FOO BAR",
+                    Env("BAZ", "from-env"),
+                    EnvFile(".env"),
+                    Env("FOO", "overridden")
+                );
+                assert_eq!(output, "overridden\nfrom-file-from-env\n");
+            });
+        }
+
+        #[test]
+        fn references_can_refer_to_the_parents_environment() {
+            in_temporary_directory(|| {
+                fs::write(".env", "FOO=${PATH}\n").unwrap();
+                let StdoutTrimmed(output) =
+                    run_output!(test_helper(), %"echo FOO", EnvFile(".env"));
+                assert_eq!(output, std::env::var("PATH").unwrap());
+            });
+        }
+
+        #[test]
+        fn parse_errors_point_at_file_and_line() {
+            in_temporary_directory(|| {
+                fs::write(".env", "FOO=foo\n# comment\nBAR='bar\n").unwrap();
+                let result: Result<(), Error> = run_result!("true", EnvFile(".env"));
+                let error = result.unwrap_err();
+                assert_eq!(
+                    error.to_string(),
+                    "true:\n  could not parse env file '.env', line 3: unterminated single quote"
+                );
+                match error {
+                    Error::ParseEnvFile { path, line, .. } => {
+                        assert_eq!(path, PathBuf::from(".env"));
+                        assert_eq!(line, 3);
+                    }
+                    error => panic!("unexpected error: {:?}", error),
+                }
+            });
+        }
+
+        #[test]
+        fn missing_files_are_io_errors() {
+            in_temporary_directory(|| {
+                let result: Result<(), Error> = run_result!("true", EnvFile("does-not-exist"));
+                match result.unwrap_err() {
+                    Error::CommandIoError { message, source } => {
+                        assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+                        assert!(
+                            message
+                                .starts_with("true:\n  could not read env file 'does-not-exist': "),
+                            "{}",
+                            message
+                        );
+                    }
+                    error => panic!("unexpected error: {:?}", error),
+                }
+            });
+        }
+
+        #[test]
+        fn read_errors_keep_their_source() {
+            use std::error::Error as _;
+
+            in_temporary_directory(|| {
+                let result: Result<(), Error> = run_result!("true", EnvFile("does-not-exist"));
+                let error = result.unwrap_err();
+                let source = error
+                    .source()
+                    .and_then(|source| source.source())
+                    .and_then(|source| source.downcast_ref::<std::io::Error>())
+                    .expect("io::Error source");
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            });
         }
 
         #[test]
-        fn failing_commands_return_oks_when_exit_status_is_captured() {
-            let Status(exit_status) = run_result!("false").unwrap();
-            assert!(!exit_status.success());
+        fn resolves_relative_paths_against_the_working_directory() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                fs::write("dir/.env", "FOO=foo\n").unwrap();
+                let StdoutTrimmed(output) = run_output!(
+                    CurrentDir("dir"),
+                    test_helper(),
+                    %"echo FOO",
+                    EnvFile(".env")
+                );
+                assert_eq!(output, "foo");
+            });
+        }
+
+        #[test]
+        fn errors_prevent_spawning_the_child() {
+            in_temporary_directory(|| {
+                fs::write(".env", "invalid\n").unwrap();
+                let result: Result<(), Error> = run_result!(%"touch file", EnvFile(".env"));
+                assert!(result.is_err());
+                assert!(!PathBuf::from("file").exists());
+            });
         }
     }
 
-    mod bool_output {
+    mod path_modifications {
         use super::*;
+        use pretty_assertions::assert_eq;
+        use std::env::{join_paths, split_paths};
+
+        fn child_path(input: impl Input) -> Vec<PathBuf> {
+            let StdoutTrimmed(path) = run_output!(test_helper(), %"echo PATH", input);
+            split_paths(&path).collect()
+        }
+
+        fn parent_path() -> Vec<PathBuf> {
+            split_paths(&std::env::var_os("PATH").unwrap()).collect()
+        }
 
         #[test]
-        fn success_exit_status_is_true() {
-            assert!(run_output!("true"));
+        fn prepends_to_the_parents_path() {
+            let mut expected = vec![PathBuf::from("/foo")];
+            expected.extend(parent_path());
+            assert_eq!(child_path(PathPrepend("/foo")), expected);
         }
 
         #[test]
-        fn failure_exit_status_is_false() {
-            assert!(!run_output!("false"));
+        fn appends_to_the_parents_path() {
+            let mut expected = parent_path();
+            expected.push(PathBuf::from("/foo"));
+            assert_eq!(child_path(PathAppend("/foo")), expected);
         }
 
         #[test]
-        #[should_panic]
-        fn io_error_panics() {
-            assert!(run_output!("/"));
+        fn modifies_path_set_with_env() {
+            let path = join_paths(vec!["/bar", "/baz"]).unwrap();
+            assert_eq!(
+                child_path((Env("PATH", path), PathPrepend("/foo"), PathAppend("/qux"))),
+                vec![
+                    PathBuf::from("/foo"),
+                    PathBuf::from("/bar"),
+                    PathBuf::from("/baz"),
+                    PathBuf::from("/qux")
+                ]
+            );
         }
-    }
 
-    mod tuple_inputs {
-        use super::*;
-        use pretty_assertions::assert_eq;
+        #[test]
+        fn env_after_path_prepend_overrides_it() {
+            assert_eq!(
+                child_path((PathPrepend("/foo"), Env("PATH", "/bar"))),
+                vec![PathBuf::from("/bar")]
+            );
+        }
 
         #[test]
-        fn two_tuple() {
-            let StdoutTrimmed(output) = run_output!(("echo", "foo"));
-            assert_eq!(output, "foo");
+        fn multiple_prepends_are_applied_in_order() {
+            let path = join_paths(vec!["/baz"]).unwrap();
+            assert_eq!(
+                child_path((Env("PATH", path), PathPrepend("/bar"), PathPrepend("/foo"))),
+                vec![
+                    PathBuf::from("/foo"),
+                    PathBuf::from("/bar"),
+                    PathBuf::from("/baz")
+                ]
+            );
         }
 
         #[test]
-        fn three_tuples() {
-            let StdoutTrimmed(output) = run_output!(("echo", "foo", "bar"));
-            assert_eq!(output, "foo bar");
+        fn empty_path_results_in_only_the_added_directory() {
+            assert_eq!(
+                child_path((Env("PATH", ""), PathAppend("/foo"))),
+                vec![PathBuf::from("/foo")]
+            );
         }
 
+        #[cfg(unix)]
         #[test]
-        fn nested_tuples() {
-            let StdoutTrimmed(output) = run_output!(("echo", ("foo", "bar")));
-            assert_eq!(output, "foo bar");
+        fn finds_executables_in_prepended_directories() {
+            use std::os::unix::fs::PermissionsExt;
+            let temp_dir = TempDir::new().unwrap();
+            let script = temp_dir.path().join("echo");
+            fs::write(&script, "#!/bin/sh\nprintf 'shadowed %s' \"$1\"\n").unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            let StdoutTrimmed(output) = run_output!("echo", "foo", PathPrepend(temp_dir.path()));
+            assert_eq!(output, "shadowed foo");
+            let StdoutTrimmed(output) = run_output!("echo", "foo", PathAppend(temp_dir.path()));
+            assert_eq!(output, "foo");
         }
 
+        #[cfg(unix)]
         #[test]
-        fn unit_input() {
-            let StdoutTrimmed(output) = run_output!(("echo", ()));
-            assert_eq!(output, "");
+        fn directories_with_separators_are_errors() {
+            let result: Result<(), Error> = run_result!("true", PathPrepend("/foo:/bar"));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "true:\n  can't add '/foo:/bar' to PATH, because it contains the path separator"
+            );
         }
     }
 
-    mod tuple_outputs {
+    mod environment_variables {
         use super::*;
+        use pretty_assertions::assert_eq;
+        use std::env;
 
         #[test]
-        fn two_tuple_1() {
-            let (StdoutTrimmed(output), Status(exit_status)) =
-                run_output!(test_helper(), "output foo and exit with 42");
-            assert_eq!(output, "foo");
-            assert_eq!(exit_status.code(), Some(42));
+        fn allows_to_add_variables() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                %"echo FOO",
+                Env("FOO", "bar")
+            );
+            assert_eq!(output, "bar");
         }
 
         #[test]
-        fn two_tuple_2() {
-            let (Status(exit_status), StdoutTrimmed(output)) =
-                run_output!(test_helper(), "output foo and exit with 42");
-            assert_eq!(output, "foo");
-            assert_eq!(exit_status.code(), Some(42));
+        fn works_for_multiple_variables() {
+            let StdoutUntrimmed(output) = run_output!(
+                test_helper(),
+                %"echo FOO BAR",
+                Env("FOO", "a"),
+                Env("BAR", "b")
+            );
+            assert_eq!(output, "a\nb\n");
         }
 
-        #[test]
-        fn result_of_tuple() {
-            let (StdoutTrimmed(output), Status(exit_status)) = run_result!(%"echo foo").unwrap();
-            assert_eq!(output, "foo");
-            assert!(exit_status.success());
+        fn find_unused_environment_variable() -> String {
+            let mut i = 0;
+            loop {
+                let key = format!("CRADLE_TEST_VARIABLE_{}", i);
+                if env::var_os(&key).is_none() {
+                    break key;
+                }
+                i += 1;
+            }
         }
 
         #[test]
-        fn result_of_tuple_when_erroring() {
-            let (StdoutTrimmed(output), Status(exit_status)) = run_result!("false").unwrap();
-            assert_eq!(output, "");
-            assert_eq!(exit_status.code(), Some(1));
+        fn child_processes_inherit_the_environment() {
+            let unused_key = find_unused_environment_variable();
+            env::set_var(&unused_key, "foo");
+            let StdoutTrimmed(output) = run_output!(test_helper(), "echo", unused_key);
+            assert_eq!(output, "foo");
         }
 
         #[test]
-        fn three_tuples() {
-            let (Stderr(stderr), StdoutTrimmed(stdout), Status(exit_status)) =
-                run_output!(%"echo foo");
-            assert_eq!(stderr, "");
-            assert_eq!(stdout, "foo");
-            assert_eq!(exit_status.code(), Some(0));
+        fn overwrites_existing_parent_variables() {
+            let unused_key = find_unused_environment_variable();
+            env::set_var(&unused_key, "foo");
+            let StdoutTrimmed(output) =
+                run_output!(test_helper(), "echo", &unused_key, Env(&unused_key, "bar"));
+            assert_eq!(output, "bar");
         }
 
         #[test]
-        fn capturing_stdout_on_errors() {
-            let (StdoutTrimmed(output), Status(exit_status)) =
-                run_output!(test_helper(), "output foo and exit with 42");
-            assert!(!exit_status.success());
-            assert_eq!(output, "foo");
+        fn variables_are_overwritten_by_subsequent_variables_with_the_same_name() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                "echo",
+                "FOO",
+                Env("FOO", "a"),
+                Env("FOO", "b"),
+            );
+            assert_eq!(output, "b");
         }
 
         #[test]
-        fn capturing_stderr_on_errors() {
-            let (Stderr(output), Status(exit_status)) =
-                run_output!(test_helper(), "write to stderr and exit with 42");
-            assert!(!exit_status.success());
-            assert_eq!(output, "foo\n");
+        fn variables_can_be_set_to_the_empty_string() {
+            let StdoutUntrimmed(output) =
+                run_output!(test_helper(), "echo", "FOO", Env("FOO", ""),);
+            assert_eq!(output, "empty variable: FOO\n");
         }
     }
 
-    mod current_dir {
+    #[cfg(unix)]
+    mod kill_on_drop {
         use super::*;
-        use std::path::Path;
+        use nix::{
+            sys::{signal::kill, wait::waitpid},
+            unistd::Pid,
+        };
+        use std::{io, panic};
+
+        #[derive(Clone, Debug)]
+        struct PanickingWriter;
+
+        impl Write for PanickingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                panic!("PanickingWriter: write")
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        fn run_with_panicking_stdout<I: Input>(input: I) -> Pid {
+            let context = Context {
+                stdout: PanickingWriter,
+                stderr: PanickingWriter,
+            };
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                run_result_with_context_unit(context, input)
+            }));
+            assert!(result.is_err());
+            Pid::from_raw(fs::read_to_string("pid").unwrap().trim().parse().unwrap())
+        }
+
+        fn is_running(pid: Pid) -> bool {
+            kill(pid, None).is_ok()
+        }
 
         #[test]
-        fn sets_the_working_directory() {
+        fn kills_the_child_when_a_relaying_thread_panics() {
             in_temporary_directory(|| {
-                fs::create_dir("dir").unwrap();
-                fs::write("dir/file", "foo").unwrap();
-                fs::write("file", "wrong file").unwrap();
-                let StdoutUntrimmed(output) = run_output!(%"cat file", CurrentDir("dir"));
-                assert_eq!(output, "foo");
+                let pid = run_with_panicking_stdout((
+                    test_helper(),
+                    "write pid to file, stream chunk then wait for file",
+                ));
+                assert!(!is_running(pid));
             });
         }
 
         #[test]
-        fn works_for_other_types() {
+        fn does_not_kill_the_child_when_disabled() {
             in_temporary_directory(|| {
-                fs::create_dir("dir").unwrap();
-                let dir: String = "dir".to_string();
-                run!("true", CurrentDir(dir));
-                let dir: PathBuf = PathBuf::from("dir");
-                run!("true", CurrentDir(dir));
-                let dir: &Path = Path::new("dir");
-                run!("true", CurrentDir(dir));
+                let pid = run_with_panicking_stdout((
+                    test_helper(),
+                    "write pid to file, stream chunk then wait for file",
+                    KillOnDrop(false),
+                ));
+                assert!(is_running(pid));
+                fs::write("file", "").unwrap();
+                waitpid(pid, None).unwrap();
             });
         }
-    }
-
-    mod capturing_stdout {
-        use super::*;
 
-        mod trimmed {
+        #[cfg(target_os = "linux")]
+        mod process_groups {
             use super::*;
 
-            #[test]
-            fn trims_trailing_whitespace() {
-                let StdoutTrimmed(output) = run_output!(%"echo foo");
-                assert_eq!(output, "foo");
-            }
-
-            #[test]
-            fn trims_leading_whitespace() {
-                let StdoutTrimmed(output) = run_output!(%"echo -n", " foo");
-                assert_eq!(output, "foo");
-            }
-
-            #[test]
-            fn does_not_remove_whitespace_within_output() {
-                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo bar");
-                assert_eq!(output, "foo bar");
-            }
-
-            #[test]
-            fn does_not_modify_output_without_whitespace() {
-                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo");
-                assert_eq!(output, "foo");
+            /// Orphaned grandchildren get reaped by some other process,
+            /// so they may linger as zombies for a while after being killed.
+            fn is_running_and_not_a_zombie(pid: Pid) -> bool {
+                match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+                    Ok(stat) => !stat.contains(") Z "),
+                    Err(_) => false,
+                }
             }
 
-            #[test]
-            fn does_not_relay_stdout() {
-                let context = Context::test();
-                let StdoutTrimmed(_) =
-                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
-                assert_eq!(context.stdout(), "");
+            /// Signals are delivered to grandchildren asynchronously,
+            /// so they may still be running for a short while
+            /// after the child has been reaped.
+            fn is_stopped_eventually(pid: Pid) -> bool {
+                for _ in 0..100 {
+                    if !is_running_and_not_a_zombie(pid) {
+                        return true;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                false
             }
-        }
 
-        mod untrimmed {
-            use super::*;
+            const SPAWN_GRANDCHILD: &str = "sleep 1000 & echo $! > pid; echo foo; wait";
 
             #[test]
-            fn does_not_trim_trailing_newline() {
-                let StdoutUntrimmed(output) = run_output!(%"echo foo");
-                assert_eq!(output, "foo\n");
+            fn kills_grandchildren_in_new_process_groups() {
+                in_temporary_directory(|| {
+                    let grandchild =
+                        run_with_panicking_stdout(("sh", "-c", SPAWN_GRANDCHILD, NewProcessGroup));
+                    assert!(is_stopped_eventually(grandchild));
+                });
             }
 
             #[test]
-            fn does_not_trim_leading_whitespace() {
-                let StdoutUntrimmed(output) = run_output!(%"echo -n", " foo");
-                assert_eq!(output, " foo");
+            fn kills_grandchildren_in_new_sessions() {
+                in_temporary_directory(|| {
+                    let grandchild =
+                        run_with_panicking_stdout(("sh", "-c", SPAWN_GRANDCHILD, NewSession));
+                    assert!(is_stopped_eventually(grandchild));
+                });
             }
 
             #[test]
-            fn does_not_relay_stdout() {
-                let context = Context::test();
-                let StdoutUntrimmed(_) =
-                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
-                assert_eq!(context.stdout(), "");
+            fn does_not_kill_grandchildren_without_a_new_process_group() {
+                in_temporary_directory(|| {
+                    let grandchild = run_with_panicking_stdout(("sh", "-c", SPAWN_GRANDCHILD));
+                    assert!(is_running_and_not_a_zombie(grandchild));
+                    kill(grandchild, nix::sys::signal::Signal::SIGKILL).unwrap();
+                });
             }
         }
     }
 
-    mod split {
+    mod handles {
         use super::*;
+        use pretty_assertions::assert_eq;
 
         #[test]
-        fn splits_words_by_whitespace() {
-            let StdoutTrimmed(output) = run_output!(Split("echo foo"));
+        fn wait_returns_outputs() {
+            let handle = spawn!(%"echo foo").unwrap();
+            let StdoutTrimmed(output) = handle.wait().unwrap();
             assert_eq!(output, "foo");
         }
 
         #[test]
-        fn skips_multiple_whitespace_characters() {
-            let StdoutUntrimmed(output) = run_output!("echo", Split("foo  bar"));
-            assert_eq!(output, "foo bar\n");
+        fn wait_returns_errors() {
+            let handle: Handle<()> = spawn!("false").unwrap();
+            assert_eq!(
+                handle.wait().unwrap_err().to_string(),
+                "false:\n  exited with exit code: 1"
+            );
         }
 
         #[test]
-        fn trims_leading_whitespace() {
-            let StdoutTrimmed(output) = run_output!(Split(" echo foo"));
-            assert_eq!(output, "foo");
+        fn spawning_errors_are_returned_right_away() {
+            let result: Result<Handle<()>, Error> = spawn!("does-not-exist");
+            match result {
+                Err(Error::FileNotFound { .. }) => {}
+                _ => panic!("should match Error::FileNotFound"),
+            }
+        }
+
+        #[test]
+        fn relays_stdout_while_running() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                let handle: Handle<()> = crate::input::spawn_with_context(
+                    context.clone(),
+                    (test_helper(), "stream chunk then wait for file"),
+                )
+                .unwrap();
+                while (context.stdout()) != "foo\n" {
+                    std::thread::sleep(std::time::Duration::from_secs_f32(0.05));
+                }
+                run!(%"touch file");
+                handle.wait().unwrap();
+            });
         }
 
         #[test]
-        fn trims_trailing_whitespace() {
-            let StdoutUntrimmed(output) = run_output!("echo", Split("foo "));
-            assert_eq!(output, "foo\n");
+        fn works_with_the_input_method() {
+            let StdoutTrimmed(output) = ("echo", "foo").spawn().unwrap().wait().unwrap();
+            assert_eq!(output, "foo");
         }
 
-        mod percent_sign {
+        #[cfg(unix)]
+        mod signals {
             use super::*;
+            use nix::{sys::signal::kill, unistd::Pid};
+            use pretty_assertions::assert_eq;
+            use std::time::{Duration, Instant};
+
+            fn is_running(pid: u32) -> bool {
+                kill(Pid::from_raw(pid as i32), None).is_ok()
+            }
 
             #[test]
-            fn splits_words() {
-                let StdoutUntrimmed(output) = run_output!(%"echo foo");
-                assert_eq!(output, "foo\n");
+            fn allows_to_send_signals() {
+                let handle = spawn!(%"sleep 1000").unwrap();
+                handle.signal(Signal::SIGINT).unwrap();
+                let Status(status) = handle.wait().unwrap();
+                assert_eq!(
+                    std::os::unix::process::ExitStatusExt::signal(&status),
+                    Some(Signal::SIGINT as i32)
+                );
             }
 
             #[test]
-            fn works_for_later_arguments() {
-                let StdoutUntrimmed(output) = run_output!("echo", %"foo\tbar");
-                assert_eq!(output, "foo bar\n");
+            fn signals_can_be_handled_by_the_child() {
+                let handle = spawn!(
+                    "sh",
+                    "-c",
+                    "trap 'echo caught; exit 0' USR1; echo started; while true; do sleep 0.01; done",
+                )
+                .unwrap();
+                std::thread::sleep(Duration::from_millis(200));
+                handle.signal(Signal::SIGUSR1).unwrap();
+                let StdoutTrimmed(output) = handle.wait().unwrap();
+                assert_eq!(output, "started\ncaught");
             }
 
             #[test]
-            fn for_first_of_multiple_arguments() {
-                let StdoutUntrimmed(output) = run_output!(%"echo foo", "bar");
-                assert_eq!(output, "foo bar\n");
+            fn dropping_a_handle_kills_the_child() {
+                let handle: Handle<()> = spawn!(%"sleep 1000").unwrap();
+                let pid = handle.id();
+                drop(handle);
+                assert!(!is_running(pid));
             }
 
             #[test]
-            fn non_literals() {
-                let command = "echo foo";
-                let StdoutUntrimmed(output) = run_output!(%command);
-                assert_eq!(output, "foo\n");
+            fn stop_uses_the_configured_signal() {
+                let handle = spawn!(
+                    %"sleep 1000",
+                    GracefulStop {
+                        signal: Signal::SIGINT,
+                        grace: Duration::from_secs(1),
+                    },
+                )
+                .unwrap();
+                let Status(status) = handle.stop().unwrap();
+                assert_eq!(
+                    std::os::unix::process::ExitStatusExt::signal(&status),
+                    Some(Signal::SIGINT as i32)
+                );
             }
 
             #[test]
-            fn in_run() {
-                run!(%"echo foo");
+            fn children_ignoring_the_signal_are_killed_after_the_grace_period() {
+                let handle = spawn!(
+                    "sh",
+                    "-c",
+                    "trap '' TERM; echo started; while true; do sleep 0.01; done",
+                    GracefulStop {
+                        signal: Signal::SIGTERM,
+                        grace: Duration::from_millis(200),
+                    },
+                )
+                .unwrap();
+                std::thread::sleep(Duration::from_millis(200));
+                let start = Instant::now();
+                let Status(status) = handle.stop().unwrap();
+                assert!(start.elapsed() >= Duration::from_millis(200));
+                assert_eq!(
+                    std::os::unix::process::ExitStatusExt::signal(&status),
+                    Some(Signal::SIGKILL as i32)
+                );
             }
 
             #[test]
-            fn in_run_result() {
-                let StdoutTrimmed(_) = run_result!(%"echo foo").unwrap();
+            fn non_zero_exit_code_errors_mention_the_signal() {
+                let result: Result<(), Error> = run_result!("sh", "-c", "kill -9 $$");
+                assert_eq!(
+                    result.unwrap_err().to_string(),
+                    "sh -c 'kill -9 $$':\n  killed by SIGKILL"
+                );
             }
         }
     }
 
-    mod splitting_with_library_functions {
+    #[cfg(feature = "session")]
+    mod sessions {
         use super::*;
+        use crate::input::session_with_context;
+        use pretty_assertions::assert_eq;
+        use std::time::Duration;
 
-        #[test]
-        fn allow_to_use_split() {
-            let StdoutTrimmed(output) = run_output!("echo foo".split(' '));
-            assert_eq!(output, "foo");
-        }
+        const TIMEOUT: Duration = Duration::from_secs(5);
 
         #[test]
-        fn split_whitespace() {
-            let StdoutTrimmed(output) = run_output!("echo foo".split_whitespace());
-            assert_eq!(output, "foo");
+        fn allows_to_send_and_expect() {
+            let mut session = session!(test_helper(), "reverse lines").unwrap();
+            session.send_line("foo").unwrap();
+            session.expect("oof", TIMEOUT).unwrap();
+            session.send_line("bar").unwrap();
+            session.expect("rab", TIMEOUT).unwrap();
         }
 
         #[test]
-        fn split_ascii_whitespace() {
-            let StdoutTrimmed(output) = run_output!("echo foo".split_ascii_whitespace());
-            assert_eq!(output, "foo");
+        fn returns_the_output_before_and_of_the_match() {
+            let mut session = session!("cat").unwrap();
+            session.send_line("foo bar baz").unwrap();
+            let expected = session.expect("b.r", TIMEOUT).unwrap();
+            assert_eq!(
+                expected,
+                Expected {
+                    before: "foo ".to_string(),
+                    matched: "bar".to_string(),
+                }
+            );
         }
-    }
-
-    mod paths {
-        use super::*;
-        use pretty_assertions::assert_eq;
-        use std::path::Path;
 
-        fn write_test_script() -> PathBuf {
-            if cfg!(unix) {
-                let file = PathBuf::from("./test-script");
-                let script = "#!/usr/bin/env bash\necho test-output\n";
-                fs::write(&file, script).unwrap();
-                run!(%"chmod +x test-script");
-                file
-            } else {
-                let file = PathBuf::from("./test-script.bat");
-                let script = "@echo test-output\n";
-                fs::write(&file, script).unwrap();
-                file
+        #[test]
+        fn only_searches_output_after_the_previous_match() {
+            let mut session = session!("cat").unwrap();
+            session.send_line("foo bar foo").unwrap();
+            session.expect("foo", TIMEOUT).unwrap();
+            let expected = session.expect("foo", TIMEOUT).unwrap();
+            assert_eq!(expected.before, " bar ");
+            let result = session.expect("foo", Duration::from_millis(50));
+            match result {
+                Err(Error::ExpectTimeout { .. }) => {}
+                _ => panic!("should match Error::ExpectTimeout"),
             }
         }
 
         #[test]
-        fn ref_path_as_argument() {
-            in_temporary_directory(|| {
-                let file: &Path = Path::new("file");
-                fs::write(file, "test-contents").unwrap();
-                let StdoutUntrimmed(output) = run_output!("cat", file);
-                assert_eq!(output, "test-contents");
-            })
+        fn times_out() {
+            let mut session = session!("cat").unwrap();
+            session.send("foo").unwrap();
+            let error = session
+                .expect("bar", Duration::from_millis(50))
+                .unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "cat:\n  timed out waiting for pattern \"bar\", unmatched output: \"foo\""
+            );
         }
 
         #[test]
-        fn ref_path_as_executable() {
-            in_temporary_directory(|| {
-                let file: &Path = &write_test_script();
-                let StdoutTrimmed(output) = run_output!(file);
-                assert_eq!(output, "test-output");
-            })
+        fn errors_when_stdout_is_closed_before_the_pattern_matches() {
+            let mut session = session!(%"echo foo").unwrap();
+            let error = session.expect("bar", TIMEOUT).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "echo foo:\n  stdout closed before pattern \"bar\" matched, unmatched output: \"foo\\n\""
+            );
         }
 
+        #[rustversion::since(1.43)]
         #[test]
-        fn path_buf_as_argument() {
-            in_temporary_directory(|| {
-                let file: PathBuf = PathBuf::from("file");
-                fs::write(&file, "test-contents").unwrap();
-                let StdoutUntrimmed(output) = run_output!("cat", file);
-                assert_eq!(output, "test-contents");
-            })
+        fn timeouts_that_are_too_long_to_represent_mean_no_timeout() {
+            let mut session = session!("cat").unwrap();
+            session.send_line("foo").unwrap();
+            let expected = session
+                .expect("foo", Duration::from_secs(u64::MAX))
+                .unwrap();
+            assert_eq!(expected.matched, "foo");
         }
 
         #[test]
-        fn path_buf_as_executable() {
-            in_temporary_directory(|| {
-                let file: PathBuf = write_test_script();
-                let StdoutTrimmed(output) = run_output!(file);
-                assert_eq!(output, "test-output");
-            })
+        fn invalid_patterns() {
+            let mut session = session!("cat").unwrap();
+            let result = session.expect("(", TIMEOUT);
+            match result {
+                Err(Error::InvalidPattern { .. }) => {}
+                _ => panic!("should match Error::InvalidPattern"),
+            }
         }
-    }
-
-    mod stdin {
-        use super::*;
 
         #[test]
-        fn allows_to_pass_in_strings_as_stdin() {
-            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin("foo"));
-            assert_eq!(output, "oof");
+        fn records_a_transcript() {
+            let mut session = session!(test_helper(), "reverse lines").unwrap();
+            session.send_line("foo").unwrap();
+            session.expect("oof\n", TIMEOUT).unwrap();
+            session.send_line("bar").unwrap();
+            session.expect("rab\n", TIMEOUT).unwrap();
+            assert_eq!(session.transcript(), "foo\noof\nbar\nrab\n");
         }
 
         #[test]
-        fn allows_passing_in_u8_slices_as_stdin() {
-            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(&[0, 1, 2]));
-            assert_eq!(output, "\x02\x01\x00");
+        fn does_not_relay_stdout() {
+            let context = Context::test();
+            let mut session = session_with_context(context.clone(), "cat").unwrap();
+            session.send_line("foo").unwrap();
+            session.expect("foo", TIMEOUT).unwrap();
+            session.wait().unwrap();
+            assert_eq!(context.stdout(), "");
         }
 
         #[test]
-        #[cfg(unix)]
-        fn stdin_is_closed_by_default() {
-            let StdoutTrimmed(output) = run_output!(test_helper(), "wait until stdin is closed");
-            assert_eq!(output, "stdin is closed");
+        fn relays_stderr() {
+            let context = Context::test();
+            let session =
+                session_with_context(context.clone(), (test_helper(), "write to stderr")).unwrap();
+            session.wait().unwrap();
+            assert_eq!(context.stderr(), "foo\n");
         }
 
         #[test]
-        fn writing_too_many_bytes_into_a_non_reading_child_may_error() {
-            let big_string = String::from_utf8(vec![b'a'; 2_usize.pow(16) + 1]).unwrap();
-            let result: Result<(), crate::Error> = run_result!("true", Stdin(big_string));
-            let message = result.unwrap_err().to_string();
-            assert!(if cfg!(unix) {
-                message == "true:\n  Broken pipe (os error 32)"
-            } else {
-                [
-                    "true:\n  The pipe is being closed. (os error 232)",
-                    "true:\n  The pipe has been ended. (os error 109)",
-                ]
-                .contains(&message.as_str())
-            });
+        fn writes_stdin_inputs_first() {
+            let mut session = session!("cat", Stdin("foo\n")).unwrap();
+            session.send_line("bar").unwrap();
+            let expected = session.expect("bar", TIMEOUT).unwrap();
+            assert_eq!(expected.before, "foo\n");
         }
 
         #[test]
-        fn multiple_stdin_arguments_are_all_passed_into_the_child_process() {
-            let StdoutUntrimmed(output) =
-                run_output!(test_helper(), "reverse", Stdin("foo"), Stdin("bar"));
-            assert_eq!(output, "raboof");
+        fn wait_closes_stdin_and_returns_the_exit() {
+            let session = session!(test_helper(), "wait until stdin is closed").unwrap();
+            assert_eq!(session.wait().unwrap(), Exit::Code(0));
+            let session = session!(test_helper(), "exit code 42").unwrap();
+            assert_eq!(session.wait().unwrap(), Exit::Code(42));
         }
 
         #[test]
-        fn works_for_owned_strings() {
-            let argument: String = "foo".to_string();
-            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(argument));
-            assert_eq!(output, "oof");
+        fn wait_discards_unreceived_output() {
+            let session = session!("sh", "-c", "yes | head -n 100000").unwrap();
+            assert_eq!(session.wait().unwrap(), Exit::Code(0));
         }
-    }
 
-    mod invocation_syntax {
-        use super::*;
+        #[test]
+        fn sending_after_closing_stdin_fails() {
+            let mut session = session!("cat").unwrap();
+            session.close_stdin();
+            assert!(session.send("foo").is_err());
+        }
 
         #[test]
-        fn trailing_comma_is_accepted_after_normal_argument() {
-            run!("echo", "foo",);
-            let StdoutUntrimmed(_) = run_output!("echo", "foo",);
-            let _result: Result<(), Error> = run_result!("echo", "foo",);
+        #[cfg(unix)]
+        fn works_with_pty() {
+            let mut session = session!("cat", Pty).unwrap();
+            session.send_line("foo").unwrap();
+            let expected = session.expect("foo\r\nfoo\r\n", TIMEOUT).unwrap();
+            assert_eq!(expected.before, "");
         }
 
         #[test]
-        fn trailing_comma_is_accepted_after_split_argument() {
-            run!("echo", %"foo",);
-            let StdoutUntrimmed(_) = run_output!("echo", %"foo",);
-            let _result: Result<(), Error> = run_result!("echo", %"foo",);
+        #[cfg(unix)]
+        fn wait_ends_pty_sessions() {
+            let mut session = session!("cat", Pty).unwrap();
+            session.send_line("foo").unwrap();
+            session.expect("foo\r\nfoo\r\n", TIMEOUT).unwrap();
+            assert_eq!(session.wait().unwrap(), Exit::Code(0));
         }
     }
 
-    mod environment_variables {
+    #[cfg(target_os = "linux")]
+    mod pty {
         use super::*;
         use pretty_assertions::assert_eq;
-        use std::env;
 
         #[test]
-        fn allows_to_add_variables() {
+        fn child_runs_in_a_terminal() {
             let StdoutTrimmed(output) = run_output!(
-                test_helper(),
-                %"echo FOO",
-                Env("FOO", "bar")
+                "sh",
+                "-c",
+                "test -t 0 && test -t 1 && test -t 2 && echo terminal",
+                Pty
             );
-            assert_eq!(output, "bar");
+            assert_eq!(output, "terminal");
         }
 
         #[test]
-        fn works_for_multiple_variables() {
-            let StdoutUntrimmed(output) = run_output!(
-                test_helper(),
-                %"echo FOO BAR",
-                Env("FOO", "a"),
-                Env("BAR", "b")
-            );
-            assert_eq!(output, "a\nb\n");
+        fn without_pty_child_does_not_run_in_a_terminal() {
+            let success: bool = run_output!("test", "-t", "1");
+            assert!(!success);
         }
 
-        fn find_unused_environment_variable() -> String {
-            let mut i = 0;
-            loop {
-                let key = format!("CRADLE_TEST_VARIABLE_{}", i);
-                if env::var_os(&key).is_none() {
-                    break key;
-                }
-                i += 1;
-            }
+        #[test]
+        fn relays_output_from_the_terminal() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (Split("echo foo"), Pty)).unwrap();
+            assert_eq!(context.stdout(), "foo\r\n");
         }
 
         #[test]
-        fn child_processes_inherit_the_environment() {
-            let unused_key = find_unused_environment_variable();
-            env::set_var(&unused_key, "foo");
-            let StdoutTrimmed(output) = run_output!(test_helper(), "echo", unused_key);
-            assert_eq!(output, "foo");
+        fn merges_stderr_into_stdout() {
+            let (StdoutUntrimmed(stdout), Stderr(stderr)) =
+                run_output!("sh", "-c", "echo foo; echo bar >&2", Pty);
+            assert_eq!(stdout, "foo\r\nbar\r\n");
+            assert_eq!(stderr, "");
         }
 
         #[test]
-        fn overwrites_existing_parent_variables() {
-            let unused_key = find_unused_environment_variable();
-            env::set_var(&unused_key, "foo");
-            let StdoutTrimmed(output) =
-                run_output!(test_helper(), "echo", &unused_key, Env(&unused_key, "bar"));
-            assert_eq!(output, "bar");
+        fn does_not_lose_output_when_the_child_exits() {
+            let StdoutUntrimmed(output) = run_output!("seq", "1", "10000", Pty);
+            assert_eq!(output.lines().count(), 10000);
         }
 
         #[test]
-        fn variables_are_overwritten_by_subsequent_variables_with_the_same_name() {
+        fn is_the_controlling_terminal() {
+            let StdoutTrimmed(output) = run_output!("sh", "-c", "tty > /dev/tty", Pty);
+            assert!(output.starts_with("/dev/pts/"), "{:?}", output);
+        }
+
+        #[test]
+        fn writes_stdin_to_the_terminal() {
+            let StdoutUntrimmed(output) = run_output!(%"sed q", Stdin("foo\n"), Pty);
+            assert_eq!(output, "foo\r\nfoo\r\n");
+        }
+
+        #[test]
+        fn sends_end_of_file_without_stdin() {
+            let StdoutUntrimmed(output) = run_output!("cat", Pty);
+            assert_eq!(output, "");
+        }
+
+        #[test]
+        fn sends_end_of_file_after_stdin() {
+            let StdoutUntrimmed(output) = run_output!("cat", Stdin("foo\nbar\n"), Pty);
+            assert_eq!(output, "foo\r\nbar\r\nfoo\r\nbar\r\n");
+        }
+
+        #[test]
+        fn sends_end_of_file_after_an_unterminated_line() {
+            let StdoutUntrimmed(output) = run_output!("cat", Stdin("foo"), Pty);
+            assert_eq!(output, "foofoo");
+        }
+
+        #[test]
+        fn forwards_window_size_changes() {
+            use crate::{
+                input::PtySize,
+                pty::{Pty, WindowSizeForwarder},
+            };
+            use nix::{libc, pty::Winsize};
+            use std::{
+                os::unix::io::AsRawFd,
+                sync::{Arc, Mutex},
+                time::{Duration, Instant},
+            };
+
+            let master = Pty::open(Some(PtySize {
+                rows: 10,
+                columns: 20,
+            }))
+            .unwrap()
+            .into_master();
+            let parent_size = Arc::new(Mutex::new(None));
+            let source = parent_size.clone();
+            let _forwarder = WindowSizeForwarder::spawn(master.try_clone().unwrap(), move || {
+                *source.lock().unwrap()
+            });
+            let size = || {
+                let mut winsize = Winsize {
+                    ws_row: 0,
+                    ws_col: 0,
+                    ws_xpixel: 0,
+                    ws_ypixel: 0,
+                };
+                unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCGWINSZ as _, &mut winsize) };
+                (winsize.ws_row, winsize.ws_col)
+            };
+            *parent_size.lock().unwrap() = Some(PtySize {
+                rows: 30,
+                columns: 40,
+            });
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while size() != (30, 40) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(size(), (30, 40));
+        }
+
+        #[test]
+        fn uses_a_default_size() {
+            let StdoutTrimmed(output) = run_output!(%"stty size", Pty);
+            assert_ne!(output, "0 0");
+        }
+
+        #[test]
+        fn allows_to_configure_the_size() {
             let StdoutTrimmed(output) = run_output!(
-                test_helper(),
-                "echo",
-                "FOO",
-                Env("FOO", "a"),
-                Env("FOO", "b"),
+                %"stty size",
+                Pty,
+                PtySize {
+                    rows: 40,
+                    columns: 100
+                }
             );
-            assert_eq!(output, "b");
+            assert_eq!(output, "40 100");
         }
 
         #[test]
-        fn variables_can_be_set_to_the_empty_string() {
-            let StdoutUntrimmed(output) =
-                run_output!(test_helper(), "echo", "FOO", Env("FOO", ""),);
-            assert_eq!(output, "empty variable: FOO\n");
+        fn exit_codes() {
+            let exit: Exit = run_output!(test_helper(), "exit code 42", Pty);
+            assert_eq!(exit, Exit::Code(42));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    mod process_group_ids {
+        use super::*;
+
+        fn pid_pgid_and_sid<I: Input>(input: I) -> Vec<String> {
+            let StdoutTrimmed(output) =
+                run_output!("sh", "-c", "ps -o pid= -o pgid= -o sid= -p $$", input);
+            output.split_whitespace().map(String::from).collect()
+        }
+
+        #[test]
+        fn children_inherit_the_process_group_by_default() {
+            let ids = pid_pgid_and_sid(());
+            assert_ne!(ids[0], ids[1]);
+        }
+
+        #[test]
+        fn new_process_group() {
+            let ids = pid_pgid_and_sid(NewProcessGroup);
+            assert_eq!(ids[0], ids[1]);
+            assert_ne!(ids[0], ids[2]);
+        }
+
+        #[test]
+        fn new_session() {
+            let ids = pid_pgid_and_sid(NewSession);
+            assert_eq!(ids[0], ids[1]);
+            assert_eq!(ids[0], ids[2]);
         }
     }
 
@@ -59,6 +59,51 @@ macro_rules! run_result {
     }}
 }
 
+/// Starts a child process without waiting for it to finish,
+/// and returns a [`Handle`](crate::handle::Handle) to it,
+/// wrapped in a [`Result<Handle<T>, Error>`](crate::handle::Handle).
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let handle = spawn!(%"echo foo").unwrap();
+/// let StdoutTrimmed(output) = handle.wait().unwrap();
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// Like [`run_output!`], [`spawn!`] uses return-type polymorphism:
+/// The type parameter `T` of the returned [`Handle`](crate::handle::Handle)
+/// determines what the child process's outputs are collected into.
+#[macro_export]
+macro_rules! spawn {
+    ($($args:tt)*) => {{
+        $crate::input::Input::spawn($crate::tuple_up!($($args)*))
+    }}
+}
+
+/// Starts a child process for scripted interaction, without waiting for it to finish,
+/// and returns a [`Session`](crate::session::Session) with it,
+/// wrapped in a [`Result<Session, Error>`](crate::session::Session).
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::time::Duration;
+///
+/// let mut session = session!("cat").unwrap();
+/// session.send_line("foo").unwrap();
+/// let expected = session.expect("f.o", Duration::from_secs(5)).unwrap();
+/// assert_eq!(expected.matched, "foo");
+/// ```
+///
+/// Only available with the `session` feature.
+#[cfg(feature = "session")]
+#[macro_export]
+macro_rules! session {
+    ($($args:tt)*) => {{
+        $crate::input::Input::session($crate::tuple_up!($($args)*))
+    }}
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! tuple_up {
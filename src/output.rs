@@ -1,7 +1,7 @@
 //! The [`Output`] trait that defines all possible outputs of a child process.
 
 use crate::{child_output::ChildOutput, config::Config, error::Error};
-use std::process::ExitStatus;
+use std::{ffi::OsString, fmt, process::ExitStatus, str::FromStr};
 
 /// All possible return types of [`run!`], [`run_output!`] or
 /// [`run_result!`] must implement this trait.
@@ -35,7 +35,13 @@ use std::process::ExitStatus;
 ///   - [`StdoutTrimmed`]: To capture `stdout`, trimmed of whitespace.
 ///   - [`StdoutUntrimmed`]: To capture `stdout` untrimmed.
 ///   - [`Stderr`]: To capture `stderr`.
+///   - [`StdoutLines`] and [`StderrLines`]: To capture `stdout` or `stderr` as lines.
+///   - [`StdoutNulSeparated`]: To capture NUL-separated records, e.g. file names.
+///   - [`Parsed`]: To parse `stdout` into other types.
+///   - `Json` and `JsonLines`: To deserialize `stdout` with `serde`.
+///     (Only available with the `serde` feature.)
 /// - [`Status`]: To capture the command's [`ExitStatus`].
+/// - [`Exit`]: To capture how the command terminated, in a platform-independent way.
 ///
 /// Also, [`Output`] is implemented for tuples.
 /// You can use this to combine multiple return types that implement [`Output`].
@@ -62,6 +68,26 @@ use std::process::ExitStatus;
 /// See
 /// [Issue 184: Provide a better API for writing custom Output impls](https://github.com/soenkehahn/cradle/issues/184)
 /// for more details and discussion.
+///
+/// If you just want to collect multiple outputs into a struct with named fields,
+/// enable the `macros` feature and use `#[derive(Output)]` instead:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(feature = "macros")]
+/// # {
+/// #[derive(Output)]
+/// struct Outputs {
+///     stdout: StdoutTrimmed,
+///     status: Status,
+/// }
+///
+/// let outputs: Outputs = run_output!(%"echo foo");
+/// assert_eq!(outputs.stdout.0, "foo");
+/// assert!(outputs.status.0.success());
+/// # }
+/// ```
 pub trait Output: Sized {
     /// Configures the given [`Config`](crate::config::Config) for the [`Output`] type.
     /// This is an internal function that should be ignored.
@@ -177,6 +203,46 @@ impl Output for StdoutTrimmed {
     }
 }
 
+/// Parses what the child process writes to `stdout` into a value of type `T`,
+/// using [`FromStr`].
+/// As with [`StdoutTrimmed`], leading and trailing whitespace is trimmed
+/// before parsing:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Parsed(number): Parsed<u64> = run_output!(%"echo 42");
+/// assert_eq!(number, 42);
+/// ```
+///
+/// If parsing fails, [`Error::ParseStdout`] is returned.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Parsed<T>(pub T);
+
+impl<T> Output for Parsed<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutTrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutTrimmed(stdout) = StdoutTrimmed::from_child_output(config, child_output)?;
+        match stdout.parse() {
+            Ok(value) => Ok(Parsed(value)),
+            Err(source) => Err(Error::ParseStdout {
+                full_command: config.full_command(),
+                stdout,
+                source: Box::new(source),
+            }),
+        }
+    }
+}
+
 /// Same as [`StdoutTrimmed`], but does not trim whitespace from the output:
 ///
 /// ```
@@ -250,6 +316,247 @@ impl Output for Stderr {
     }
 }
 
+/// Captures what the child process writes to `stdout` as a vector of lines.
+/// Lines can be terminated by `\n` or `\r\n`, the line terminators
+/// are not included.
+/// A trailing line terminator does not result in an additional empty line:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutLines(lines) = run_output!("printf", "foo\nbar\r\n");
+/// assert_eq!(lines, vec!["foo", "bar"]);
+/// ```
+///
+/// To process lines while the child process is still running,
+/// see [`StdoutStream`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutLines(pub Vec<String>);
+
+impl Output for StdoutLines {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        Ok(StdoutLines(stdout.lines().map(ToOwned::to_owned).collect()))
+    }
+}
+
+/// Captures what the child process writes to `stdout` as a list of
+/// NUL-terminated records, as written by e.g. `find -print0`
+/// or `git ls-files -z`:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+/// use std::ffi::OsString;
+///
+/// run!("touch", "foo bar");
+/// let StdoutNulSeparated(files) = run_output!(%"find . -type f -print0");
+/// assert_eq!(files, vec![OsString::from("./foo bar")]);
+/// ```
+///
+/// A trailing NUL byte does not result in an additional empty record.
+/// In contrast to [`StdoutUntrimmed`], [`StdoutNulSeparated`] doesn't require
+/// the output to be valid utf-8 on unix,
+/// so it works for arbitrary file names.
+/// On other platforms, records are converted from utf-8 lossily.
+///
+/// See also [`StdinNulSeparated`](crate::input::StdinNulSeparated).
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutNulSeparated(pub Vec<OsString>);
+
+impl Output for StdoutNulSeparated {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        config.capture_stdout = true;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let stdout = child_output
+            .stdout
+            .as_ref()
+            .ok_or_else(|| Error::internal("stdout not captured", config))?;
+        let stdout = match stdout.split_last() {
+            Some((0, init)) => init,
+            _ => stdout.as_slice(),
+        };
+        if stdout.is_empty() {
+            return Ok(StdoutNulSeparated(Vec::new()));
+        }
+        Ok(StdoutNulSeparated(
+            stdout
+                .split(|byte| *byte == 0)
+                .map(bytes_to_os_string)
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes).to_os_string()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Same as [`StdoutLines`], but for `stderr`:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StderrLines(lines) = run_output!("sh", "-c", "echo foo >&2; echo bar >&2");
+/// assert_eq!(lines, vec!["foo", "bar"]);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct StderrLines(pub Vec<String>);
+
+impl Output for StderrLines {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        Stderr::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let Stderr(stderr) = Stderr::from_child_output(config, child_output)?;
+        Ok(StderrLines(stderr.lines().map(ToOwned::to_owned).collect()))
+    }
+}
+
+/// Streams what the child process writes to `stdout`,
+/// for use with [`Handle`](crate::handle::Handle)s.
+/// [`Handle::stdout_lines`](crate::handle::Handle::stdout_lines)
+/// then returns an iterator over the lines of `stdout`,
+/// that yields them as soon as the child writes them:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let mut handle: Handle<StdoutStream> = spawn!("printf", "foo\nbar\n").unwrap();
+/// for line in handle.stdout_lines().unwrap() {
+///     let line = line.unwrap();
+///     println!("got line: {}", line);
+/// }
+/// handle.wait().unwrap();
+/// ```
+///
+/// `stdout` is neither relayed to the parent's `stdout`, nor collected.
+/// Using [`StdoutStream`] with [`run_output!`] just discards `stdout`.
+///
+/// Only a small amount of `stdout` is buffered.
+/// When the lines are not received, the child blocks on writing to `stdout`,
+/// until the [`StdoutLineStream`](crate::handle::StdoutLineStream) is dropped,
+/// or [`Handle::wait`](crate::handle::Handle::wait) is called.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutStream;
+
+impl Output for StdoutStream {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        config.stream_stdout = true;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(_config: &Config, _child_output: &ChildOutput) -> Result<Self, Error> {
+        Ok(StdoutStream)
+    }
+}
+
+/// Deserializes what the child process writes to `stdout` as JSON
+/// into a value of type `T`:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::collections::BTreeMap;
+///
+/// let Json(map): Json<BTreeMap<String, u32>> = run_output!("echo", r#"{"foo": 42}"#);
+/// assert_eq!(map["foo"], 42);
+/// ```
+///
+/// If deserialization fails, [`Error::ParseJson`] is returned.
+///
+/// Only available with the `serde` feature.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "serde")]
+impl<T> Output for Json<T>
+where
+    T: serde_crate::de::DeserializeOwned,
+{
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        serde_json::from_str(&stdout)
+            .map(Json)
+            .map_err(|source| Error::parse_json(config, &stdout, source))
+    }
+}
+
+/// Deserializes every line that the child process writes to `stdout` as JSON
+/// into a value of type `T`.
+/// This is also known as [JSON Lines](https://jsonlines.org/)
+/// or newline-delimited JSON.
+/// Empty lines are skipped.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let JsonLines(numbers): JsonLines<u32> = run_output!("printf", "1\n2\n3\n");
+/// assert_eq!(numbers, vec![1, 2, 3]);
+/// ```
+///
+/// If deserialization fails, [`Error::ParseJson`] is returned.
+///
+/// Only available with the `serde` feature.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct JsonLines<T>(pub Vec<T>);
+
+#[cfg(feature = "serde")]
+impl<T> Output for JsonLines<T>
+where
+    T: serde_crate::de::DeserializeOwned,
+{
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        let mut result = Vec::new();
+        for line in stdout.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            result.push(
+                serde_json::from_str(line)
+                    .map_err(|source| Error::parse_json(config, line, source))?,
+            );
+        }
+        Ok(JsonLines(result))
+    }
+}
+
 /// Use [`Status`] as the return type for [`run_output!`] to retrieve the
 /// [`ExitStatus`] of the child process:
 ///
@@ -293,6 +600,125 @@ impl Output for Status {
     }
 }
 
+/// [`Exit`] describes how a child process terminated.
+/// Like [`Status`] it suppresses errors for non-zero exit codes,
+/// but it can be matched on without platform-specific extension traits:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let exit: Exit = run_output!("false");
+/// assert_eq!(exit, Exit::Code(1));
+///
+/// # #[cfg(unix)]
+/// # {
+/// let exit: Exit = run_output!("sh", "-c", "kill -9 $$");
+/// match exit {
+///     Exit::Signal { signal: 9, .. } => {}
+///     _ => panic!("expected SIGKILL"),
+/// }
+/// # }
+/// ```
+///
+/// [`Exit`] implements [`Display`](fmt::Display),
+/// rendering signals by name, e.g. `killed by SIGKILL (core dumped)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The child process exited with the given exit code.
+    Code(i32),
+    /// The child process was terminated by the given signal.
+    /// This only happens on unix.
+    Signal { signal: i32, core_dumped: bool },
+}
+
+impl Exit {
+    /// Returns `true` if the child process exited with exit code `0`.
+    pub fn success(&self) -> bool {
+        *self == Exit::Code(0)
+    }
+}
+
+impl From<ExitStatus> for Exit {
+    fn from(exit_status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = exit_status.signal() {
+                return Exit::Signal {
+                    signal,
+                    core_dumped: core_dumped(&exit_status),
+                };
+            }
+        }
+        // Waiting for children never returns statuses of stopped processes,
+        // so processes that weren't killed by a signal always have an exit code.
+        Exit::Code(
+            exit_status
+                .code()
+                .expect("exit status should have either an exit code or a signal"),
+        )
+    }
+}
+
+#[cfg(unix)]
+#[rustversion::since(1.58)]
+fn core_dumped(exit_status: &ExitStatus) -> bool {
+    std::os::unix::process::ExitStatusExt::core_dumped(exit_status)
+}
+
+#[cfg(unix)]
+#[rustversion::before(1.58)]
+fn core_dumped(_exit_status: &ExitStatus) -> bool {
+    false
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exit::Code(exit_code) => write!(f, "exit code: {}", exit_code),
+            Exit::Signal {
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "killed by ")?;
+                write_signal_name(f, *signal)?;
+                if *core_dumped {
+                    write!(f, " (core dumped)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn write_signal_name(f: &mut fmt::Formatter<'_>, signal: i32) -> fmt::Result {
+    use crate::handle::Signal;
+    use std::convert::TryFrom;
+
+    match Signal::try_from(signal) {
+        Ok(signal) => write!(f, "{}", signal),
+        Err(_) => write!(f, "signal {}", signal),
+    }
+}
+
+#[cfg(not(unix))]
+fn write_signal_name(f: &mut fmt::Formatter<'_>, signal: i32) -> fmt::Result {
+    write!(f, "signal {}", signal)
+}
+
+impl Output for Exit {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        config.error_on_non_zero_exit_code = false;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(_config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        Ok(Exit::from(child_output.exit_status))
+    }
+}
+
 /// Using [`bool`] as the return type for [`run_output!`] will return `true` if
 /// the command returned successfully, and `false` otherwise:
 ///
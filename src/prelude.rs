@@ -7,4 +7,6 @@
 //! see the documentation in the [crate root](crate).
 
 include!("common_re_exports.rs.snippet");
-pub use crate::{run, run_output, run_result};
+#[cfg(feature = "session")]
+pub use crate::session;
+pub use crate::{run, run_output, run_result, spawn};
@@ -0,0 +1,292 @@
+//! An internal module for running child processes attached to pseudo-terminals.
+
+use crate::input::PtySize;
+use nix::{libc, pty::Winsize};
+use std::{
+    ffi::{CStr, OsStr, OsString},
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::{
+        raw::c_char,
+        unix::{
+            ffi::OsStrExt,
+            fs::OpenOptionsExt,
+            io::{AsRawFd, RawFd},
+        },
+    },
+    process::Stdio,
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+#[derive(Debug)]
+pub(crate) struct Pty {
+    master: File,
+    slave: File,
+}
+
+impl Pty {
+    /// Opens a new pseudo-terminal with the given size.
+    /// If no size is given, the size of the parent's terminal is used,
+    /// falling back to 24 rows and 80 columns.
+    pub(crate) fn open(size: Option<PtySize>) -> io::Result<Pty> {
+        let winsize = window_size(size.or_else(parent_window_size).unwrap_or(PtySize {
+            rows: 24,
+            columns: 80,
+        }));
+        // In contrast to `openpty`, this opens both sides with `O_CLOEXEC`
+        // set atomically (the standard library always sets it).
+        // Otherwise child processes that are spawned concurrently
+        // could inherit the file descriptors.
+        let master = open_terminal(OsStr::new("/dev/ptmx"))?;
+        let fd = master.as_raw_fd();
+        check(unsafe { libc::grantpt(fd) })?;
+        check(unsafe { libc::unlockpt(fd) })?;
+        check(unsafe { libc::ioctl(fd, libc::TIOCSWINSZ as _, &winsize) })?;
+        let slave = open_terminal(&slave_name(&master)?)?;
+        Ok(Pty { master, slave })
+    }
+
+    pub(crate) fn slave_stdio(&self) -> io::Result<Stdio> {
+        Ok(Stdio::from(self.slave.try_clone()?))
+    }
+
+    /// Closes the slave side and returns the master side.
+    pub(crate) fn into_master(self) -> File {
+        self.master
+    }
+}
+
+fn open_terminal(path: &OsStr) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(path)
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the path of the slave side of the pseudo-terminal.
+/// Uses thread-safe alternatives to `ptsname`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn slave_name(master: &File) -> io::Result<OsString> {
+    let mut buffer = [0 as c_char; 128];
+    let result = unsafe { libc::ptsname_r(master.as_raw_fd(), buffer.as_mut_ptr(), buffer.len()) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+    Ok(unsafe { c_string_to_os_string(buffer.as_ptr()) })
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn slave_name(master: &File) -> io::Result<OsString> {
+    let mut buffer = [0 as c_char; 128];
+    check(unsafe {
+        libc::ioctl(
+            master.as_raw_fd(),
+            libc::TIOCPTYGNAME as _,
+            buffer.as_mut_ptr(),
+        )
+    })?;
+    Ok(unsafe { c_string_to_os_string(buffer.as_ptr()) })
+}
+
+/// `ptsname` returns a pointer into a static buffer,
+/// so calls to it are serialized.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+fn slave_name(master: &File) -> io::Result<OsString> {
+    use lazy_static::lazy_static;
+    use std::sync::Mutex;
+
+    lazy_static! {
+        static ref PTSNAME_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    let _lock = PTSNAME_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let name = unsafe { libc::ptsname(master.as_raw_fd()) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { c_string_to_os_string(name) })
+}
+
+/// `name` must point to a NUL-terminated string.
+unsafe fn c_string_to_os_string(name: *const c_char) -> OsString {
+    let name = CStr::from_ptr(name);
+    OsStr::from_bytes(name.to_bytes()).to_os_string()
+}
+
+fn window_size(PtySize { rows, columns }: PtySize) -> Winsize {
+    Winsize {
+        ws_row: rows,
+        ws_col: columns,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+/// Returns the size of the terminal that the parent is running in, if any.
+pub(crate) fn parent_window_size() -> Option<PtySize> {
+    let fds: [RawFd; 3] = [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO];
+    for fd in fds.iter() {
+        let mut winsize = window_size(PtySize {
+            rows: 0,
+            columns: 0,
+        });
+        let result = unsafe { libc::ioctl(*fd, libc::TIOCGWINSZ as _, &mut winsize) };
+        if result == 0 && winsize.ws_row > 0 && winsize.ws_col > 0 {
+            return Some(PtySize {
+                rows: winsize.ws_row,
+                columns: winsize.ws_col,
+            });
+        }
+    }
+    None
+}
+
+/// How often [`WindowSizeForwarder`] checks the size of the parent's terminal.
+const WINDOW_SIZE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Forwards changes of the size of the parent's terminal
+/// to a pseudo-terminal, until it's dropped.
+///
+/// Setting the size on the master side makes the kernel send `SIGWINCH`
+/// to the child.
+/// The parent's terminal is polled instead of handling `SIGWINCH` in the parent,
+/// because a library can't install a signal handler without replacing
+/// the one of the application.
+#[derive(Debug)]
+pub(crate) struct WindowSizeForwarder {
+    _stop: Sender<()>,
+}
+
+impl WindowSizeForwarder {
+    pub(crate) fn spawn(
+        master: File,
+        size_source: impl Fn() -> Option<PtySize> + Send + 'static,
+    ) -> WindowSizeForwarder {
+        let (stop, stopped) = channel();
+        let mut last = size_source();
+        thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(WINDOW_SIZE_POLL_INTERVAL)
+            {
+                let current = size_source();
+                if current != last {
+                    if let Some(size) = current {
+                        let _ = set_window_size(&master, size);
+                    }
+                    last = current;
+                }
+            }
+        });
+        WindowSizeForwarder { _stop: stop }
+    }
+}
+
+fn set_window_size(master: &File, size: PtySize) -> io::Result<()> {
+    let winsize = window_size(size);
+    check(unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ as _, &winsize) })
+}
+
+/// Makes the pseudo-terminal on the child's `stdin` its controlling terminal.
+/// To be called from `pre_exec`, after `setsid`.
+pub(crate) fn set_controlling_terminal() -> io::Result<()> {
+    if unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY as _, 0) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Reads from the master side of a pseudo-terminal.
+///
+/// On linux, reading from the master fails with `EIO` once all
+/// processes have closed the slave side. This is turned into
+/// an end-of-file here.
+#[derive(Debug)]
+pub(crate) struct MasterReader(pub(crate) File);
+
+impl Read for MasterReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buffer) {
+            Err(error) if error.raw_os_error() == Some(libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+}
+
+/// Writes to the master side of a pseudo-terminal.
+///
+/// The master side can't be closed to signal the end of the child's input,
+/// since that would hang up the terminal.
+/// So, when dropped, this writes the terminal's end-of-file character
+/// (usually `^D`) instead, which makes reads from the child's `stdin`
+/// return an end-of-file, like with a closed pipe.
+/// This only works if the terminal is in canonical mode, which is the default.
+#[derive(Debug)]
+pub(crate) struct MasterWriter {
+    master: File,
+    at_line_start: bool,
+}
+
+impl MasterWriter {
+    pub(crate) fn new(master: File) -> MasterWriter {
+        MasterWriter {
+            master,
+            at_line_start: true,
+        }
+    }
+
+    fn end_of_file_character(&self) -> u8 {
+        let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+        if unsafe { libc::tcgetattr(self.master.as_raw_fd(), termios.as_mut_ptr()) } == 0 {
+            let termios = unsafe { termios.assume_init() };
+            termios.c_cc[libc::VEOF]
+        } else {
+            0x04
+        }
+    }
+}
+
+impl Write for MasterWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let written = self.master.write(buffer)?;
+        if let Some(last) = buffer[..written].last() {
+            self.at_line_start = *last == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.master.flush()
+    }
+}
+
+impl Drop for MasterWriter {
+    fn drop(&mut self) {
+        let eof = self.end_of_file_character();
+        // On a non-empty line, the first end-of-file character
+        // only submits the line.
+        let eofs = if self.at_line_start {
+            vec![eof]
+        } else {
+            vec![eof, eof]
+        };
+        let _ = self.master.write_all(&eofs);
+    }
+}
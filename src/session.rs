@@ -0,0 +1,260 @@
+//! The [`Session`] type for scripted interaction with child processes.
+//!
+//! Only available with the `session` feature.
+
+#[cfg(unix)]
+use crate::handle::Signal;
+use crate::{
+    collected_output::OpenStdin, config::Config, context::Context, error::Error, handle::Handle,
+    output::Exit,
+};
+use regex::bytes::Regex;
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError},
+    time::{Duration, Instant},
+};
+
+/// An interactive session with a running child process,
+/// as returned by [`session!`] and [`Input::session`](crate::input::Input::session).
+///
+/// In contrast to [`Stdin`](crate::input::Stdin), which writes all bytes to the child's
+/// `stdin` up front, a [`Session`] allows to write to the child incrementally,
+/// and to wait for patterns to appear on its `stdout` in between,
+/// in the style of [`expect`](https://en.wikipedia.org/wiki/Expect):
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::time::Duration;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let mut session = session!("sh").unwrap();
+/// session.send_line("echo $((6 * 7))").unwrap();
+/// let expected = session.expect(r"\d+", Duration::from_secs(5)).unwrap();
+/// assert_eq!(expected.matched, "42");
+/// session.send_line("exit 3").unwrap();
+/// let exit = session.wait().unwrap();
+/// assert_eq!(exit, Exit::Code(3));
+/// # }
+/// ```
+///
+/// The child's `stdout` is consumed by the session and not relayed to the parent's `stdout`.
+/// `stderr` is relayed as usual.
+/// Everything written to the child and read from its `stdout` is recorded
+/// in the [transcript](Session::transcript).
+///
+/// When a [`Session`] is dropped without waiting for the child,
+/// the child is stopped, in the same way as when a [`Handle`] is dropped.
+///
+/// Only available with the `session` feature.
+#[derive(Debug)]
+pub struct Session {
+    handle: Handle<Exit>,
+    stdin: Option<OpenStdin>,
+    stdout: Receiver<Vec<u8>>,
+    unmatched: Vec<u8>,
+    transcript: Vec<u8>,
+    eof: bool,
+    /// The patterns passed to [`Session::expect`] so far, compiled.
+    regexes: HashMap<String, Regex>,
+}
+
+/// The output of a child process that [`Session::expect`] waited for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expected {
+    /// The output before the match.
+    pub before: String,
+    /// The output that matched the pattern.
+    pub matched: String,
+}
+
+impl Session {
+    pub(crate) fn spawn<Stdout, Stderr>(
+        context: Context<Stdout, Stderr>,
+        mut config: Config,
+    ) -> Result<Session, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        config.keep_stdin_open = true;
+        config.stream_stdout = true;
+        let mut handle = Handle::spawn(context, config)?;
+        let stdin = handle.take_open_stdin();
+        let stdout = handle
+            .take_stdout_chunks()
+            .ok_or_else(|| Error::internal("stdout chunks not available", handle.config()))?;
+        let mut session = Session {
+            handle,
+            stdin,
+            stdout,
+            unmatched: Vec::new(),
+            transcript: Vec::new(),
+            eof: false,
+            regexes: HashMap::new(),
+        };
+        if let Some(stdin) = session.handle.config().stdin.clone() {
+            session.send(stdin.as_slice())?;
+        }
+        Ok(session)
+    }
+
+    /// Returns the OS-assigned process identifier of the child process.
+    pub fn id(&self) -> u32 {
+        self.handle.id()
+    }
+
+    /// Sends `signal` to the child process. See [`Handle::signal`].
+    ///
+    /// Only available on unix.
+    #[cfg(unix)]
+    pub fn signal(&self, signal: Signal) -> Result<(), Error> {
+        self.handle.signal(signal)
+    }
+
+    /// Writes `bytes` to the child's `stdin`.
+    pub fn send(&mut self, bytes: impl AsRef<[u8]>) -> Result<(), Error> {
+        let bytes = bytes.as_ref();
+        // Keeps the transcript in order.
+        self.receive_available();
+        let config = self.handle.config();
+        let OpenStdin(stdin) = self.stdin.as_mut().ok_or_else(|| {
+            Error::command_io_error(
+                config,
+                std::io::Error::new(std::io::ErrorKind::BrokenPipe, "stdin is closed"),
+            )
+        })?;
+        stdin
+            .write_all(bytes)
+            .and_then(|()| stdin.flush())
+            .map_err(|error| Error::command_io_error(config, error))?;
+        self.transcript.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn receive_available(&mut self) {
+        loop {
+            match self.stdout.try_recv() {
+                Ok(chunk) => self.receive(&chunk),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.eof = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn receive(&mut self, chunk: &[u8]) {
+        self.unmatched.extend_from_slice(chunk);
+        self.transcript.extend_from_slice(chunk);
+    }
+
+    /// Writes `line` and a newline to the child's `stdin`.
+    pub fn send_line(&mut self, line: impl AsRef<str>) -> Result<(), Error> {
+        self.send(format!("{}\n", line.as_ref()))
+    }
+
+    /// Closes the child's `stdin`.
+    /// Many interactive programs exit when their `stdin` is closed.
+    /// With [`Pty`](crate::input::Pty), the terminal's end-of-file
+    /// character is sent instead.
+    pub fn close_stdin(&mut self) {
+        self.stdin = None;
+    }
+
+    /// Waits until the output of the child matches the regular expression `pattern`,
+    /// for at most `timeout`.
+    ///
+    /// Output that was read in previous calls to [`Session::expect`] is
+    /// not searched again, i.e. every call only considers output after the
+    /// previous match.
+    /// The output up to the end of the match is returned as an [`Expected`] value.
+    ///
+    /// Returns [`Error::ExpectTimeout`] if the pattern doesn't match within `timeout`
+    /// and [`Error::ExpectEof`] if the child closes its `stdout` before the pattern matches.
+    /// Invalid patterns result in [`Error::InvalidPattern`].
+    ///
+    /// Timeouts that are too long to be represented (e.g. `u64::MAX` seconds)
+    /// are treated as no timeout.
+    pub fn expect(&mut self, pattern: &str, timeout: Duration) -> Result<Expected, Error> {
+        let regex = self.compile(pattern)?;
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            if let Some((start, end)) = regex.find(&self.unmatched).map(|m| (m.start(), m.end())) {
+                let rest = self.unmatched.split_off(end);
+                let consumed = std::mem::replace(&mut self.unmatched, rest);
+                return Ok(Expected {
+                    before: String::from_utf8_lossy(&consumed[..start]).into_owned(),
+                    matched: String::from_utf8_lossy(&consumed[start..]).into_owned(),
+                });
+            }
+            if self.eof {
+                return Err(Error::ExpectEof {
+                    full_command: self.handle.config().full_command(),
+                    pattern: pattern.to_string(),
+                    unmatched: String::from_utf8_lossy(&self.unmatched).into_owned(),
+                });
+            }
+            let received = match deadline {
+                Some(deadline) => self
+                    .stdout
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self
+                    .stdout
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(chunk) => self.receive(&chunk),
+                Err(RecvTimeoutError::Disconnected) => self.eof = true,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(Error::ExpectTimeout {
+                        full_command: self.handle.config().full_command(),
+                        pattern: pattern.to_string(),
+                        unmatched: String::from_utf8_lossy(&self.unmatched).into_owned(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Compiles `pattern`, or returns the [`Regex`] compiled in an earlier call.
+    fn compile(&mut self, pattern: &str) -> Result<Regex, Error> {
+        if let Some(regex) = self.regexes.get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern).map_err(|source| Error::InvalidPattern {
+            pattern: pattern.to_string(),
+            source: Box::new(source),
+        })?;
+        self.regexes.insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+
+    /// Returns everything that was written to the child's `stdin`
+    /// and read from its `stdout` so far, in the order it happened.
+    pub fn transcript(&self) -> String {
+        String::from_utf8_lossy(&self.transcript).into_owned()
+    }
+
+    /// Closes the child's `stdin` and waits for the child to exit.
+    ///
+    /// Non-zero exit codes are not turned into errors,
+    /// but returned as [`Exit`] values.
+    pub fn wait(mut self) -> Result<Exit, Error> {
+        self.close_stdin();
+        // Unreceived output would otherwise block the child.
+        drop(self.stdout);
+        self.handle.wait()
+    }
+
+    /// Stops the child process, as configured by
+    /// [`GracefulStop`](crate::input::GracefulStop). See [`Handle::stop`].
+    pub fn stop(self) -> Result<Exit, Error> {
+        drop(self.stdout);
+        self.handle.stop()
+    }
+}
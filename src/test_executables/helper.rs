@@ -1,5 +1,5 @@
 use std::{
-    io::{self, Read, Write},
+    io::{self, BufRead, Read, Write},
     path::PathBuf,
     thread::sleep,
     time::Duration,
@@ -20,6 +20,15 @@ fn main() {
                 sleep(Duration::from_secs_f32(0.1));
             }
         }
+        "write pid to file, stream chunk then wait for file" => {
+            std::fs::write("./pid", std::process::id().to_string()).unwrap();
+            println!("foo");
+            io::stdout().flush().unwrap();
+            let file = PathBuf::from("./file");
+            while !file.exists() {
+                sleep(Duration::from_secs_f32(0.1));
+            }
+        }
         "output foo and exit with 42" => {
             println!("foo");
             std::process::exit(42)
@@ -45,6 +54,13 @@ fn main() {
             io::stdout().write_all(&input).unwrap();
             io::stdout().flush().unwrap();
         }
+        "reverse lines" => {
+            for line in io::stdin().lock().lines() {
+                let line = line.unwrap().chars().rev().collect::<String>();
+                println!("{}", line);
+                io::stdout().flush().unwrap();
+            }
+        }
         "wait until stdin is closed" => {
             while !stdin_is_closed() {}
             println!("stdin is closed");
@@ -0,0 +1,196 @@
+//! The [`Which`] type for resolving executables against `PATH`.
+
+use crate::{
+    config::Config,
+    error::{Error, NotFoundReason},
+};
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Resolves an executable name against the directories in `PATH`,
+/// in the same way `cradle` does before spawning a child process on unix:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let path = Which("sh").resolve().unwrap();
+/// assert!(path.is_absolute());
+/// assert!(path.ends_with("sh"));
+/// # }
+/// ```
+///
+/// Names that contain a path separator, like `./foo`, are not searched for
+/// in `PATH`, but returned unchanged.
+/// If the executable can't be found, [`Which::resolve`] returns an
+/// [`Error::FileNotFound`] that lists the directories that were searched:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use cradle::error::NotFoundReason;
+///
+/// let result = Which("does-not-exist").resolve();
+/// match result {
+///     Err(Error::FileNotFound {
+///         reason: NotFoundReason::NotInPath { searched },
+///         ..
+///     }) => assert!(!searched.is_empty()),
+///     _ => panic!(),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Which<T: AsRef<OsStr>>(pub T);
+
+impl<T> Which<T>
+where
+    T: AsRef<OsStr>,
+{
+    /// Resolves the executable, using the `PATH` of the current process.
+    pub fn resolve(&self) -> Result<PathBuf, Error> {
+        let executable = self.0.as_ref();
+        resolve(executable, std::env::var_os("PATH").as_deref(), None).map_err(|reason| {
+            Error::FileNotFound {
+                executable: executable.to_os_string(),
+                source: io::Error::from(io::ErrorKind::NotFound),
+                reason,
+            }
+        })
+    }
+}
+
+/// Resolves the executable of a child process,
+/// honoring a `PATH` set with [`Env`](crate::input::Env).
+///
+/// Returns the executable unchanged if `PATH` is not set,
+/// to leave the lookup to the standard library.
+#[cfg(unix)]
+pub(crate) fn resolve_executable(executable: &OsStr, config: &Config) -> Result<PathBuf, Error> {
+    let path = config.path_variable();
+    resolve(
+        executable,
+        path.as_deref(),
+        config.working_directory.as_deref(),
+    )
+    .map_err(|reason| {
+        Error::file_not_found(
+            config,
+            executable,
+            io::Error::from(io::ErrorKind::NotFound),
+            reason,
+        )
+    })
+}
+
+/// On windows, `CreateProcess` searches more than `PATH`, e.g. the
+/// directory of the parent's executable and the system directories.
+/// So the lookup is left to it.
+#[cfg(not(unix))]
+pub(crate) fn resolve_executable(executable: &OsStr, _config: &Config) -> Result<PathBuf, Error> {
+    Ok(PathBuf::from(executable))
+}
+
+fn resolve(
+    executable: &OsStr,
+    path: Option<&OsStr>,
+    working_directory: Option<&Path>,
+) -> Result<PathBuf, NotFoundReason> {
+    let path = match path {
+        Some(path) if !contains_separator(executable) => path,
+        _ => return Ok(PathBuf::from(executable)),
+    };
+    let mut searched = Vec::new();
+    let mut not_executable = None;
+    for directory in std::env::split_paths(path) {
+        let directory = match (working_directory, directory.is_relative()) {
+            (Some(working_directory), true) => working_directory.join(directory),
+            _ => directory,
+        };
+        for candidate in candidates(&directory, executable) {
+            if candidate.is_file() {
+                if is_executable(&candidate) {
+                    return Ok(candidate);
+                }
+                not_executable.get_or_insert(candidate);
+            }
+        }
+        searched.push(directory);
+    }
+    Err(match not_executable {
+        Some(path) => NotFoundReason::NotExecutable { path },
+        None => NotFoundReason::NotInPath { searched },
+    })
+}
+
+fn contains_separator(executable: &OsStr) -> bool {
+    executable
+        .to_string_lossy()
+        .contains(std::path::is_separator)
+}
+
+#[cfg(unix)]
+fn candidates(directory: &Path, executable: &OsStr) -> Vec<PathBuf> {
+    vec![directory.join(executable)]
+}
+
+/// On windows, executables can be given without their extension,
+/// so all extensions in `PATHEXT` are tried as well.
+#[cfg(not(unix))]
+fn candidates(directory: &Path, executable: &OsStr) -> Vec<PathBuf> {
+    let mut result = vec![directory.join(executable)];
+    if Path::new(executable).extension().is_none() {
+        let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+        for extension in extensions
+            .split(';')
+            .filter(|extension| !extension.is_empty())
+        {
+            let mut file_name = executable.to_os_string();
+            file_name.push(extension);
+            result.push(directory.join(file_name));
+        }
+    }
+    result
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// Figures out why spawning an existing executable file failed with
+/// [`io::ErrorKind::NotFound`].
+/// Usually that's because the interpreter in its shebang doesn't exist.
+pub(crate) fn diagnose_not_found(executable: &Path) -> NotFoundReason {
+    match shebang_interpreter(executable) {
+        Some(interpreter) if !Path::new(&interpreter).exists() => NotFoundReason::BadInterpreter {
+            path: executable.to_path_buf(),
+            interpreter,
+        },
+        _ => NotFoundReason::Other,
+    }
+}
+
+fn shebang_interpreter(executable: &Path) -> Option<OsString> {
+    use std::io::{BufRead, BufReader, Read};
+    let file = std::fs::File::open(executable).ok()?;
+    let mut first_line = Vec::new();
+    BufReader::new(file.take(1024))
+        .read_until(b'\n', &mut first_line)
+        .ok()?;
+    if !first_line.starts_with(b"#!") {
+        return None;
+    }
+    let rest = String::from_utf8_lossy(&first_line[2..]);
+    rest.split_whitespace().next().map(OsString::from)
+}
@@ -0,0 +1,39 @@
+use cradle::prelude::*;
+
+fn echo_foo() -> String {
+    let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO");
+    output
+}
+
+// All tests for process-wide defaults are in one test function,
+// since they would otherwise interfere with each other.
+#[cfg(unix)]
+#[test]
+fn process_wide_defaults() {
+    set_defaults(Env("FOO", "global"));
+    assert_eq!(echo_foo(), "global");
+
+    // applies to other threads
+    assert_eq!(std::thread::spawn(echo_foo).join().unwrap(), "global");
+
+    // scoped defaults are added on top
+    with_defaults(Env("BAR", "scoped"), || {
+        let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO $BAR");
+        assert_eq!(output, "global scoped");
+    });
+
+    // process-wide defaults set inside of a scope apply right away
+    with_defaults(Env("BAR", "scoped"), || {
+        set_defaults(Env("FOO", "changed"));
+        let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO $BAR");
+        assert_eq!(output, "changed scoped");
+    });
+
+    // setting defaults again replaces them
+    set_defaults(Env("BAR", "bar"));
+    assert_eq!(echo_foo(), "");
+
+    clear_defaults();
+    let StdoutTrimmed(output) = run_output!(%"sh -c", "echo $FOO $BAR");
+    assert_eq!(output, "");
+}
@@ -1,3 +1,14 @@
+// Newer toolchains warn about `run_result!(...)?;` statements relying on
+// `()` as the never type fallback, about the unread fields of the
+// custom error types below, and about the size of `cradle::Error`.
+#![allow(
+    unknown_lints,
+    dependency_on_unit_never_type_fallback,
+    dead_code,
+    clippy::result_large_err,
+    clippy::large_enum_variant
+)]
+
 #[cfg(unix)]
 const WHICH: &str = "which";
 #[cfg(windows)]